@@ -1,6 +1,7 @@
 use eframe::egui;
 use image::{imageops, DynamicImage, RgbaImage};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
 // ── Data Model ──────────────────────────────────────────────────────────────
@@ -23,6 +24,15 @@ impl Color4 {
         )
     }
 
+    fn to_rgba8(&self) -> [u8; 4] {
+        [
+            (self.r * 255.0) as u8,
+            (self.g * 255.0) as u8,
+            (self.b * 255.0) as u8,
+            (self.a * 255.0) as u8,
+        ]
+    }
+
     #[allow(dead_code)]
     fn from_egui(c: egui::Color32) -> Self {
         Self {
@@ -51,6 +61,90 @@ enum Fill {
     None,
     Color(Color4),
     Blur(f32),
+    /// Averages the source image over square blocks of this many pixels,
+    /// at least `MIN_PIXELATE_BLOCK`.
+    Pixelate(#[serde(deserialize_with = "deserialize_pixelate_block")] u32),
+    /// Paints the source image solid black.
+    Redact,
+    /// Leaves the shape lit and dims the rest of the image by `dim` (0 to
+    /// 1), turning it gray too with `grayscale`.
+    Spotlight { dim: f32, grayscale: bool },
+}
+
+/// How an outline is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+enum StrokeStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl StrokeStyle {
+    const ALL: [StrokeStyle; 3] = [StrokeStyle::Solid, StrokeStyle::Dashed, StrokeStyle::Dotted];
+
+    fn label(self) -> &'static str {
+        match self {
+            StrokeStyle::Solid => "Solid",
+            StrokeStyle::Dashed => "Dashed",
+            StrokeStyle::Dotted => "Dotted",
+        }
+    }
+
+    /// The on and off lengths of a stroke `width` wide. Dots are
+    /// zero-length dashes, which round caps turn into circles.
+    fn dash_pattern(self, width: f32) -> Option<[f32; 2]> {
+        let width = width.max(1.0);
+        match self {
+            StrokeStyle::Solid => None,
+            StrokeStyle::Dashed => Some([width * 3.0, width * 2.0]),
+            StrokeStyle::Dotted => Some([0.0, width * 2.0]),
+        }
+    }
+}
+
+/// Which ends of an arrow get a head.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+enum ArrowHeads {
+    None,
+    Start,
+    #[default]
+    End,
+    Both,
+}
+
+impl ArrowHeads {
+    const ALL: [ArrowHeads; 4] = [ArrowHeads::None, ArrowHeads::Start, ArrowHeads::End, ArrowHeads::Both];
+
+    fn label(self) -> &'static str {
+        match self {
+            ArrowHeads::None => "None",
+            ArrowHeads::Start => "Start",
+            ArrowHeads::End => "End",
+            ArrowHeads::Both => "Both",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+enum HeadShape {
+    #[default]
+    Triangle,
+    /// Two strokes, like a chevron.
+    Open,
+    Dot,
+}
+
+impl HeadShape {
+    const ALL: [HeadShape; 3] = [HeadShape::Triangle, HeadShape::Open, HeadShape::Dot];
+
+    fn label(self) -> &'static str {
+        match self {
+            HeadShape::Triangle => "Triangle",
+            HeadShape::Open => "Open",
+            HeadShape::Dot => "Dot",
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -61,6 +155,16 @@ enum AnnotationKind {
         end: (f32, f32),
         color: Color4,
         thickness: f32,
+        #[serde(default)]
+        style: StrokeStyle,
+        #[serde(default)]
+        heads: ArrowHeads,
+        #[serde(default)]
+        head_shape: HeadShape,
+        /// The control point of a curved arrow, a quadratic Bézier from
+        /// `start` to `end`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        control: Option<(f32, f32)>,
     },
     Rectangle {
         min: (f32, f32),
@@ -69,6 +173,8 @@ enum AnnotationKind {
         thickness: f32,
         #[serde(default)]
         fill: Fill,
+        #[serde(default)]
+        style: StrokeStyle,
     },
     Oval {
         min: (f32, f32),
@@ -77,6 +183,8 @@ enum AnnotationKind {
         thickness: f32,
         #[serde(default)]
         fill: Fill,
+        #[serde(default)]
+        style: StrokeStyle,
     },
     Text {
         pos: (f32, f32),
@@ -84,6 +192,203 @@ enum AnnotationKind {
         font_size: f32,
         color: Color4,
     },
+    /// A freehand stroke through simplified `points`. Highlighter strokes
+    /// are multiply-blended so the text underneath stays legible.
+    Path {
+        points: Vec<(f32, f32)>,
+        color: Color4,
+        thickness: f32,
+        #[serde(default)]
+        highlight: bool,
+    },
+    /// A closed outline through `points`, which may be non-convex.
+    Polygon {
+        points: Vec<(f32, f32)>,
+        color: Color4,
+        thickness: f32,
+        #[serde(default)]
+        fill: Fill,
+    },
+    /// Straight segments through `points`, left open.
+    Polyline {
+        points: Vec<(f32, f32)>,
+        color: Color4,
+        thickness: f32,
+    },
+    /// A circled step number centered on `pos`. Steps are kept numbered
+    /// 1, 2, 3, ... by `renumber_steps`.
+    Step {
+        pos: (f32, f32),
+        number: u32,
+        color: Color4,
+        font_size: f32,
+    },
+    /// A measured distance from `start` to `end`, with ticks across both
+    /// ends and its length as a label, in the file's calibrated units if
+    /// it has any.
+    Dimension {
+        start: (f32, f32),
+        end: (f32, f32),
+        color: Color4,
+        thickness: f32,
+        font_size: f32,
+    },
+    /// The source pixels within `source_min`..`source_max`, shown `zoom`
+    /// times larger in a frame whose top-left corner is `frame`, with an
+    /// optional line joining the two.
+    Magnifier {
+        source_min: (f32, f32),
+        source_max: (f32, f32),
+        frame: (f32, f32),
+        zoom: f32,
+        color: Color4,
+        thickness: f32,
+        connector: bool,
+    },
+    /// Text wrapped inside a rounded box, with a tail out to `anchor`. The
+    /// box grows downwards when the text needs more room than `min`..`max`.
+    Callout {
+        min: (f32, f32),
+        max: (f32, f32),
+        anchor: (f32, f32),
+        content: String,
+        font_size: f32,
+        background: Color4,
+    },
+}
+
+impl AnnotationKind {
+    fn color_mut(&mut self) -> &mut Color4 {
+        match self {
+            AnnotationKind::Arrow { color, .. }
+            | AnnotationKind::Rectangle { color, .. }
+            | AnnotationKind::Oval { color, .. }
+            | AnnotationKind::Text { color, .. }
+            | AnnotationKind::Path { color, .. }
+            | AnnotationKind::Polygon { color, .. }
+            | AnnotationKind::Polyline { color, .. }
+            | AnnotationKind::Step { color, .. }
+            | AnnotationKind::Dimension { color, .. }
+            | AnnotationKind::Magnifier { color, .. } => color,
+            AnnotationKind::Callout { background, .. } => background,
+        }
+    }
+
+    /// The thickness as the toolbar shows it. Highlighter strokes are stored
+    /// `HIGHLIGHTER_WIDTH_SCALE` times wider than the setting they were
+    /// drawn with.
+    fn thickness_setting(&mut self) -> Option<f32> {
+        let scale = self.thickness_scale();
+        self.thickness_mut().map(|thickness| *thickness / scale)
+    }
+
+    fn set_thickness_setting(&mut self, setting: f32) {
+        let scale = self.thickness_scale();
+        if let Some(thickness) = self.thickness_mut() {
+            *thickness = setting * scale;
+        }
+    }
+
+    fn thickness_scale(&self) -> f32 {
+        match self {
+            AnnotationKind::Path { highlight: true, .. } => HIGHLIGHTER_WIDTH_SCALE,
+            _ => 1.0,
+        }
+    }
+
+    fn thickness_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            AnnotationKind::Arrow { thickness, .. }
+            | AnnotationKind::Rectangle { thickness, .. }
+            | AnnotationKind::Oval { thickness, .. }
+            | AnnotationKind::Path { thickness, .. }
+            | AnnotationKind::Polygon { thickness, .. }
+            | AnnotationKind::Polyline { thickness, .. }
+            | AnnotationKind::Dimension { thickness, .. }
+            | AnnotationKind::Magnifier { thickness, .. } => Some(thickness),
+            AnnotationKind::Text { .. } | AnnotationKind::Step { .. } | AnnotationKind::Callout { .. } => {
+                None
+            }
+        }
+    }
+
+    fn fill_mut(&mut self) -> Option<&mut Fill> {
+        match self {
+            AnnotationKind::Rectangle { fill, .. }
+            | AnnotationKind::Oval { fill, .. }
+            | AnnotationKind::Polygon { fill, .. } => Some(fill),
+            AnnotationKind::Arrow { .. }
+            | AnnotationKind::Text { .. }
+            | AnnotationKind::Path { .. }
+            | AnnotationKind::Polyline { .. }
+            | AnnotationKind::Step { .. }
+            | AnnotationKind::Dimension { .. }
+            | AnnotationKind::Magnifier { .. }
+            | AnnotationKind::Callout { .. } => None,
+        }
+    }
+
+    fn font_size_mut(&mut self) -> Option<&mut f32> {
+        match self {
+            AnnotationKind::Text { font_size, .. }
+            | AnnotationKind::Step { font_size, .. }
+            | AnnotationKind::Dimension { font_size, .. }
+            | AnnotationKind::Callout { font_size, .. } => Some(font_size),
+            _ => None,
+        }
+    }
+
+    fn style_mut(&mut self) -> Option<&mut StrokeStyle> {
+        match self {
+            AnnotationKind::Arrow { style, .. }
+            | AnnotationKind::Rectangle { style, .. }
+            | AnnotationKind::Oval { style, .. } => Some(style),
+            _ => None,
+        }
+    }
+
+    fn arrow_heads_mut(&mut self) -> Option<(&mut ArrowHeads, &mut HeadShape)> {
+        match self {
+            AnnotationKind::Arrow {
+                heads, head_shape, ..
+            } => Some((heads, head_shape)),
+            _ => None,
+        }
+    }
+
+    /// Every image-space point the annotation is placed by.
+    fn points(&self) -> Vec<(f32, f32)> {
+        match self {
+            AnnotationKind::Arrow { start, end, control, .. } => {
+                [*start, *end].into_iter().chain(*control).collect()
+            }
+            AnnotationKind::Rectangle { min, max, .. } | AnnotationKind::Oval { min, max, .. } => {
+                vec![*min, *max]
+            }
+            AnnotationKind::Text { pos, .. } | AnnotationKind::Step { pos, .. } => vec![*pos],
+            AnnotationKind::Path { points, .. }
+            | AnnotationKind::Polygon { points, .. }
+            | AnnotationKind::Polyline { points, .. } => points.clone(),
+            AnnotationKind::Dimension { start, end, .. } => vec![*start, *end],
+            AnnotationKind::Magnifier {
+                source_min,
+                source_max,
+                frame,
+                ..
+            } => vec![*source_min, *source_max, *frame],
+            AnnotationKind::Callout { min, max, anchor, .. } => vec![*min, *max, *anchor],
+        }
+    }
+
+    /// The text typed into a Text or Callout annotation.
+    fn content_mut(&mut self) -> Option<&mut String> {
+        match self {
+            AnnotationKind::Text { content, .. } | AnnotationKind::Callout { content, .. } => {
+                Some(content)
+            }
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -91,9 +396,132 @@ struct Annotation {
     kind: AnnotationKind,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The number the next new step gets.
+fn next_step_number(annotations: &[Annotation]) -> u32 {
+    annotations
+        .iter()
+        .filter_map(|ann| match ann.kind {
+            AnnotationKind::Step { number, .. } => Some(number),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// Numbers the steps among `added` after those in `existing`, keeping the
+/// order of their own numbers, so pasted or duplicated steps continue the
+/// sequence instead of repeating it.
+fn number_added_steps(existing: &[Annotation], added: &mut [Annotation]) {
+    let mut steps: Vec<&mut u32> = added
+        .iter_mut()
+        .filter_map(|ann| match &mut ann.kind {
+            AnnotationKind::Step { number, .. } => Some(number),
+            _ => None,
+        })
+        .collect();
+    steps.sort_by_key(|number| **number);
+    for (number, next) in steps.into_iter().zip(next_step_number(existing)..) {
+        *number = next;
+    }
+}
+
+/// Renumbers the steps 1, 2, 3, ... in the order of their current numbers,
+/// closing the gap a removed step leaves. `moved` is a step just given a
+/// new number, with its old one: it goes before the step it now shares a
+/// number with when it moved earlier, and after it when it moved later.
+fn renumber_steps(annotations: &mut [Annotation], moved: Option<(usize, u32)>) {
+    let mut steps: Vec<(u32, u8, usize)> = annotations
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ann)| match ann.kind {
+            AnnotationKind::Step { number, .. } => {
+                let tie_break = match moved {
+                    Some((index, old)) if index == i && number < old => 0,
+                    Some((index, _)) if index == i => 2,
+                    _ => 1,
+                };
+                Some((number, tie_break, i))
+            }
+            _ => None,
+        })
+        .collect();
+    steps.sort();
+    for ((_, _, i), next) in steps.into_iter().zip(1..) {
+        if let AnnotationKind::Step { number, .. } = &mut annotations[i].kind {
+            *number = next;
+        }
+    }
+}
+
+/// The image's scale, from a known span: `pixels` image pixels measure
+/// `length` `unit`s. Dimension labels use it to show real distances.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Calibration {
+    pixels: f32,
+    length: f32,
+    unit: String,
+}
+
+impl Calibration {
+    /// A distance in image pixels, in calibrated units.
+    fn to_units(&self, pixels: f32) -> f32 {
+        pixels * self.length / self.pixels
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 struct AnnotationFile {
+    version: u32,
     annotations: Vec<Annotation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    calibration: Option<Calibration>,
+}
+
+/// Upgrades from each sidecar version to the next: `MIGRATIONS[n]` turns a
+/// version `n` file into a version `n + 1` one. Files written before the
+/// `version` field existed are version 0.
+///
+/// Adding an annotation kind or field also bumps the version, with
+/// `no_migration`, so that older builds refuse the file as too new rather
+/// than calling it corrupt or dropping the fields they don't know when they
+/// save it back. The check is on the version alone: a build refuses every
+/// file with a newer version, even one that only uses what it knows.
+const MIGRATIONS: &[fn(&mut serde_json::Value)] = &[
+    migrate_v0_fill,
+    no_migration, // v2 adds Path
+    no_migration, // v3 adds Polygon and Polyline
+    no_migration, // v4 adds the Pixelate and Redact fills
+    no_migration, // v5 adds Step
+    no_migration, // v6 adds Callout
+    no_migration, // v7 adds Dimension and the calibration
+    no_migration, // v8 adds the Spotlight fill
+    no_migration, // v9 adds Magnifier
+    no_migration, // v10 adds stroke styles and arrowhead options
+    no_migration, // v11 adds curved arrows
+];
+
+/// The version written by this build.
+const ANNOTZ_VERSION: u32 = MIGRATIONS.len() as u32;
+
+fn no_migration(_file: &mut serde_json::Value) {}
+
+/// v0 → v1: rectangles and ovals predating fills get an explicit `"None"`.
+fn migrate_v0_fill(file: &mut serde_json::Value) {
+    let Some(annotations) = file
+        .get_mut("annotations")
+        .and_then(|a| a.as_array_mut())
+    else {
+        return;
+    };
+    for ann in annotations {
+        let Some(kind) = ann.get_mut("kind").and_then(|k| k.as_object_mut()) else {
+            continue;
+        };
+        if matches!(kind.get("type").and_then(|t| t.as_str()), Some("Rectangle" | "Oval")) {
+            kind.entry("fill").or_insert_with(|| "None".into());
+        }
+    }
 }
 
 fn annotz_path(image_path: &Path) -> PathBuf {
@@ -107,25 +535,338 @@ fn annotz_path(image_path: &Path) -> PathBuf {
     ))
 }
 
-fn load_annotations(image_path: &Path) -> Vec<Annotation> {
-    let path = annotz_path(image_path);
-    if path.exists() {
-        if let Ok(data) = std::fs::read_to_string(&path) {
-            if let Ok(file) = serde_json::from_str::<AnnotationFile>(&data) {
-                return file.annotations;
-            }
+/// Why a sidecar couldn't be read. A missing sidecar is not an error, it
+/// just means the image has no annotations yet.
+#[derive(Debug)]
+enum LoadError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, serde_json::Error),
+    TooNew(PathBuf, u64),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(path, e) => write!(f, "could not read {}: {e}", path.display()),
+            LoadError::Parse(path, e) => write!(f, "could not parse {}: {e}", path.display()),
+            LoadError::TooNew(path, version) => write!(
+                f,
+                "{} was written by a newer annotate-edit (format version {version}, \
+                 this build reads up to {ANNOTZ_VERSION})",
+                path.display()
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SaveError {
+    Serialize(serde_json::Error),
+    Io(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Serialize(e) => write!(f, "could not serialize annotations: {e}"),
+            SaveError::Io(path, e) => write!(f, "could not write {}: {e}", path.display()),
         }
     }
-    Vec::new()
 }
 
-fn save_annotations(image_path: &Path, annotations: &[Annotation]) {
+fn load_annotations(image_path: &Path) -> Result<AnnotationFile, LoadError> {
+    let path = annotz_path(image_path);
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(AnnotationFile::default()),
+        Err(e) => return Err(LoadError::Io(path, e)),
+    };
+    parse_annotation_file(&data).map_err(|e| match e {
+        SchemaError::Invalid(e) => LoadError::Parse(path, e),
+        SchemaError::TooNew(version) => LoadError::TooNew(path, version),
+    })
+}
+
+/// Why a serialized `AnnotationFile` couldn't be read, wherever it came
+/// from (a sidecar or the clipboard).
+#[derive(Debug)]
+enum SchemaError {
+    Invalid(serde_json::Error),
+    TooNew(u64),
+}
+
+/// Parse an `AnnotationFile`, upgrading it from older versions.
+fn parse_annotation_file(data: &str) -> Result<AnnotationFile, SchemaError> {
+    let mut file: serde_json::Value = serde_json::from_str(data).map_err(SchemaError::Invalid)?;
+    let version = match file.get("version") {
+        None => 0,
+        Some(v) => v.as_u64().ok_or_else(|| {
+            SchemaError::Invalid(serde::de::Error::custom(format!("invalid version {v}")))
+        })?,
+    };
+    if version > ANNOTZ_VERSION as u64 {
+        return Err(SchemaError::TooNew(version));
+    }
+    for migrate in &MIGRATIONS[version as usize..] {
+        migrate(&mut file);
+    }
+    if let Some(fields) = file.as_object_mut() {
+        fields.insert("version".into(), ANNOTZ_VERSION.into());
+    }
+    serde_json::from_value(file).map_err(SchemaError::Invalid)
+}
+
+/// Write the sidecar, backing up the previous one first. A failed backup
+/// doesn't stop the save; it is returned so the caller can report it.
+fn save_annotations(
+    image_path: &Path,
+    annotations: &[Annotation],
+    calibration: Option<&Calibration>,
+) -> Result<Option<String>, SaveError> {
     let path = annotz_path(image_path);
     let file = AnnotationFile {
+        version: ANNOTZ_VERSION,
         annotations: annotations.to_vec(),
+        calibration: calibration.cloned(),
+    };
+    let data = serde_json::to_string_pretty(&file).map_err(SaveError::Serialize)?;
+    let backup_error = backup_sidecar(&path)
+        .err()
+        .map(|e| format!("could not back up {}: {e}", path.display()));
+    write_atomic(&path, data.as_bytes()).map_err(|e| SaveError::Io(path, e))?;
+    Ok(backup_error)
+}
+
+/// Copy an unparseable sidecar to `<sidecar>.corrupt-<unix time>` so the
+/// original bytes survive whatever the user decides to do with it.
+fn quarantine_sidecar(image_path: &Path) -> std::io::Result<PathBuf> {
+    let path = annotz_path(image_path);
+    let mut target = path.clone().into_os_string();
+    target.push(format!(".corrupt-{}", unix_now()));
+    let target = PathBuf::from(target);
+    std::fs::copy(&path, &target)?;
+    Ok(target)
+}
+
+// ── Atomic Writes & Backups ─────────────────────────────────────────────────
+
+/// Default number of sidecar backups kept per image; override with
+/// `ANNOTATE_EDIT_BACKUPS` (0 turns backups off).
+const DEFAULT_BACKUP_COUNT: usize = 10;
+
+/// Auto-save runs on every edit, so a new backup is only taken once the
+/// newest one is at least this old. Otherwise the ring would only ever
+/// cover the last few drags.
+const BACKUP_INTERVAL_SECS: u64 = 120;
+
+/// Replace `path` with `data` so that readers see either the old file or the
+/// new one, never a partial write: the data goes to a temporary file in the
+/// same directory, is flushed to disk, and is then renamed over `path`. The
+/// new file keeps the old one's permissions, and the directory is synced so
+/// the rename itself survives a crash.
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let (tmp, mut file) = create_temporary(path)?;
+    let result = std::fs::metadata(path)
+        .map_or(Ok(()), |existing| file.set_permissions(existing.permissions()))
+        .and_then(|()| file.write_all(data))
+        .and_then(|()| file.sync_all())
+        .and_then(|()| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result?;
+    sync_parent_dir(path)
+}
+
+/// Claims a fresh temporary file next to `path`. Creating it exclusively
+/// means a stale or planted file at the same name, or a symlink there, is
+/// skipped rather than truncated or followed.
+fn create_temporary(path: &Path) -> std::io::Result<(PathBuf, std::fs::File)> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    for attempt in 0..100 {
+        let tmp = path.with_file_name(format!(
+            ".{file_name}.tmp-{}-{stamp}-{attempt}",
+            std::process::id()
+        ));
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        "no free temporary file name",
+    ))
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(dir)?.sync_all()
+}
+
+/// Directories can't be opened for syncing here; the rename is as durable
+/// as the platform makes it.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn backup_count() -> usize {
+    std::env::var("ANNOTATE_EDIT_BACKUPS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_BACKUP_COUNT)
+}
+
+/// `$XDG_CACHE_HOME/annotate-edit`, or `~/.cache/annotate-edit`.
+fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|cache| cache.join("annotate-edit"))
+}
+
+/// Where backups of `sidecar` go: a per-sidecar directory under
+/// `$XDG_CACHE_HOME/annotate-edit/backups` (or `~/.cache/...`), falling back
+/// to `<sidecar>.backups` next to it when there is no cache dir.
+fn backup_dir(sidecar: &Path) -> PathBuf {
+    let Some(cache) = cache_dir() else {
+        let mut dir = sidecar.as_os_str().to_owned();
+        dir.push(".backups");
+        return PathBuf::from(dir);
     };
-    if let Ok(data) = serde_json::to_string_pretty(&file) {
-        let _ = std::fs::write(&path, data);
+    // Key the directory on the absolute path (FNV-1a, so the name is stable
+    // across builds) so same-named images in different folders don't mix.
+    let absolute = std::path::absolute(sidecar).unwrap_or_else(|_| sidecar.to_path_buf());
+    let hash = absolute
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf29ce484222325u64, |h, &b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+    let file_name = sidecar.file_name().unwrap_or_default().to_string_lossy();
+    cache.join("backups").join(format!("{file_name}-{hash:016x}"))
+}
+
+/// Copy the sidecar about to be overwritten into its backup ring as
+/// `<unix time>.annotz`, dropping the oldest copies beyond the configured
+/// count.
+fn backup_sidecar(sidecar: &Path) -> std::io::Result<()> {
+    backup_sidecar_at(sidecar, unix_now())
+}
+
+/// `backup_sidecar` with the clock passed in.
+fn backup_sidecar_at(sidecar: &Path, now: u64) -> std::io::Result<()> {
+    let count = backup_count();
+    if count == 0 || !sidecar.exists() {
+        return Ok(());
+    }
+    let dir = backup_dir(sidecar);
+    std::fs::create_dir_all(&dir)?;
+
+    let mut stamps: Vec<u64> = std::fs::read_dir(&dir)?
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_suffix(".annotz")?.parse().ok()
+        })
+        .collect();
+    stamps.sort_unstable();
+
+    // Copies stamped in the future, from before the clock went back, don't
+    // hold off new ones.
+    if stamps
+        .iter()
+        .rev()
+        .find(|&&stamp| stamp <= now)
+        .is_some_and(|&newest| now - newest < BACKUP_INTERVAL_SECS)
+    {
+        return Ok(());
+    }
+    std::fs::copy(sidecar, dir.join(format!("{now}.annotz")))?;
+    stamps.push(now);
+
+    let excess = stamps.len().saturating_sub(count);
+    for stamp in &stamps[..excess] {
+        std::fs::remove_file(dir.join(format!("{stamp}.annotz")))?;
+    }
+    Ok(())
+}
+
+// ── System Clipboard ────────────────────────────────────────────────────────
+
+/// Claim a fresh `clipboard-<stamp>-<n>.png` in `dir` by creating it empty.
+/// Names taken by another image, or by a leftover sidecar that would attach
+/// someone else's annotations to it, are skipped.
+fn new_clipboard_path(dir: &Path, stamp: u128) -> std::io::Result<PathBuf> {
+    for attempt in 0..100 {
+        let path = dir.join(format!("clipboard-{stamp}-{attempt}.png"));
+        if annotz_path(&path).exists() {
+            continue;
+        }
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        "no free clipboard image name",
+    ))
+}
+
+fn clipboard_image(clipboard: &mut arboard::Clipboard) -> Result<RgbaImage, String> {
+    let data = clipboard.get_image().map_err(|e| e.to_string())?;
+    RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+        .ok_or_else(|| "clipboard image has an unexpected size".to_string())
+}
+
+/// Save a clipboard image as a PNG under the cache dir, so it gets a
+/// sidecar and exports like any image opened from a file.
+fn save_clipboard_image(img: &RgbaImage) -> Result<PathBuf, String> {
+    let dir = cache_dir()
+        .unwrap_or_else(|| std::env::temp_dir().join("annotate-edit"))
+        .join("clipboard");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let path = new_clipboard_path(&dir, stamp).map_err(|e| e.to_string())?;
+    let mut data = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut data), image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    write_atomic(&path, &data).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn window_title(image_path: Option<&Path>) -> String {
+    match image_path {
+        Some(path) => format!(
+            "annotate-edit — {}",
+            path.file_name().unwrap_or_default().to_str().unwrap_or("")
+        ),
+        None => "annotate-edit".to_string(),
     }
 }
 
@@ -137,6 +878,14 @@ enum Tool {
     Rectangle,
     Oval,
     Text,
+    Pen,
+    Highlighter,
+    Polygon,
+    Polyline,
+    Step,
+    Callout,
+    Dimension,
+    Magnifier,
     Select,
 }
 
@@ -145,24 +894,100 @@ enum FillMode {
     None,
     Color,
     Blur,
+    Pixelate,
+    Redact,
+    Spotlight,
 }
 
 #[derive(Clone, Debug)]
 enum DragState {
     None,
     Drawing { start: egui::Pos2 },
-    Moving { index: usize },
+    /// A freehand stroke in progress, in image space.
+    Sketching { points: Vec<egui::Pos2> },
+    Moving,
+    Resizing { index: usize, handle: Handle },
+    /// Rubber-band selection from `start` (screen space) to the pointer.
+    Marquee { start: egui::Pos2 },
+}
+
+/// Which stored coordinate of a `min`/`max` box a handle drags. Boxes keep
+/// the corners they were drawn with, so `Min` isn't necessarily the
+/// top/left one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BoxEdge {
+    Min,
+    Max,
+}
+
+/// A grab point on the selected annotation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Handle {
+    /// An end of an Arrow or Dimension.
+    ArrowStart,
+    ArrowEnd,
+    /// Corner (both axes set) or edge midpoint (one axis set) of a
+    /// Rectangle or Oval.
+    Box {
+        x: Option<BoxEdge>,
+        y: Option<BoxEdge>,
+    },
+    /// A vertex of a Polygon or Polyline.
+    Vertex(usize),
+    /// The tip of a Callout's tail.
+    Anchor,
+    /// The top-left corner of a Magnifier's frame, which moves the frame.
+    Frame,
+    /// The bottom-right corner of a Magnifier's frame, which sets the zoom.
+    Zoom,
+    /// The point halfway along an Arrow, which bends it.
+    Bend,
+}
+
+/// A sidecar problem shown in the banner under the toolbar.
+#[derive(Clone, Debug)]
+enum SidecarProblem {
+    /// The sidecar on disk couldn't be loaded. Auto-save stays off until the
+    /// user reloads it or chooses to overwrite it, so the file on disk is
+    /// never silently replaced by the empty list we started with.
+    Load {
+        error: String,
+        quarantined: Option<PathBuf>,
+        /// False for a file from a newer build, which overwriting would
+        /// downgrade.
+        overwritable: bool,
+    },
+    /// The last save failed; the next edit retries it.
+    Save(String),
+    /// The last save went through but the backup before it didn't. Shown
+    /// once per run of failures, so dismissing it sticks until backups
+    /// work again.
+    Backup(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SidecarAction {
+    Reload,
+    Overwrite,
+    Retry,
+    Dismiss,
 }
 
 // ── App ─────────────────────────────────────────────────────────────────────
 
 struct AnnotateApp {
-    image_path: PathBuf,
+    /// `None` for an empty session waiting for an image to be pasted.
+    image_path: Option<PathBuf>,
     texture: Option<egui::TextureHandle>,
+    /// The image as exports draw it. Drawn instead of `texture` while it
+    /// is current, and sampled by blurs and insets.
+    source: Option<CanvasSource>,
     image_size: (f32, f32),
     raw_image: Option<DynamicImage>,
 
     annotations: Vec<Annotation>,
+    /// Not part of undo: it changes rarely, and only through the toolbar.
+    calibration: Option<Calibration>,
     undo_stack: Vec<Vec<Annotation>>,
     redo_stack: Vec<Vec<Annotation>>,
 
@@ -173,55 +998,315 @@ struct AnnotateApp {
     fill_mode: FillMode,
     fill_color: [f32; 3],
     blur_sigma: f32,
+    pixelate_block: u32,
+    spotlight_dim: f32,
+    spotlight_grayscale: bool,
+    magnify: f32,
+    magnifier_connector: bool,
+    stroke_style: StrokeStyle,
+    arrow_heads: ArrowHeads,
+    head_shape: HeadShape,
+    /// What the selected dimension measures, entered to calibrate the
+    /// image's scale.
+    scale_length: f32,
+    scale_unit: String,
 
     drag: DragState,
-    selected: Option<usize>,
+    selected: BTreeSet<usize>,
+    /// Image-space vertices placed so far by the Polygon or Polyline tool.
+    vertices: Vec<egui::Pos2>,
+
+    sidecar_problem: Option<SidecarProblem>,
+    /// Whether the last save's backup failed.
+    backup_failing: bool,
+
+    /// Set while a toolbar edit of the selection is in progress, so that
+    /// one slider drag or one focused field makes a single undo step.
+    property_edit_open: bool,
 
     // text input state
     text_input_pos: Option<(f32, f32)>,
     text_input_buf: String,
+    /// The Text or Callout annotation being re-edited, if the input isn't
+    /// for a new one. Cleared by `cancel_text_edit` whenever the list
+    /// changes underneath it.
+    text_editing: Option<usize>,
+    /// A callout just drawn, added once its text is entered.
+    callout_draft: Option<AnnotationKind>,
 
     // pan & zoom
     pan: egui::Vec2,
     zoom: f32,
     panning: bool,
+
+    /// Kept open for the whole session: on X11 the copied image is only
+    /// served while the clipboard handle that set it is alive.
+    clipboard: Option<arboard::Clipboard>,
+    /// Set when a Ctrl+V press already tried pasting an image, so the
+    /// release doesn't try again.
+    image_paste_tried: bool,
 }
 
-impl AnnotateApp {
-    fn new(image_path: PathBuf) -> Self {
-        let annotations = load_annotations(&image_path);
-        let raw_image = image::open(&image_path).ok();
-        let image_size = raw_image
-            .as_ref()
-            .map(|img| (img.width() as f32, img.height() as f32))
-            .unwrap_or((800.0, 600.0));
+/// Highlighter strokes are this many times wider than the thickness setting.
+const HIGHLIGHTER_WIDTH_SCALE: f32 = 4.0;
+const HIGHLIGHTER_ALPHA: f32 = 0.5;
 
-        Self {
-            image_path,
-            texture: None,
-            image_size,
-            raw_image,
-            annotations,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
-            tool: Tool::Arrow,
-            color: [1.0, 0.0, 0.0],
-            thickness: 3.0,
-            font_size: 20.0,
-            fill_mode: FillMode::None,
-            fill_color: [1.0, 1.0, 0.0],
-            blur_sigma: 8.0,
-            drag: DragState::None,
-            selected: None,
-            text_input_pos: None,
-            text_input_buf: String::new(),
-            pan: egui::Vec2::ZERO,
-            zoom: 1.0,
-            panning: false,
-        }
-    }
+/// How far (in screen pixels) a freehand stroke may stray from the
+/// simplified path that gets stored.
+const PATH_TOLERANCE: f32 = 1.5;
 
-    fn current_color4(&self) -> Color4 {
+/// How far (in image pixels) Ctrl+D places duplicates from the originals.
+const DUPLICATE_OFFSET: f32 = 12.0;
+
+/// Screen-space half size of the square resize handles.
+const HANDLE_RADIUS: f32 = 4.0;
+
+/// Points in the polygon a dashed oval is drawn along on the canvas.
+const ELLIPSE_SEGMENTS: usize = 96;
+
+/// Straight segments a curved arrow is drawn and hit-tested along.
+const CURVE_SEGMENTS: usize = 32;
+
+/// The ranges of the stroke thickness, font size and blur amount sliders.
+const MIN_THICKNESS: f32 = 1.0;
+const MAX_THICKNESS: f32 = 20.0;
+const MIN_FONT_SIZE: f32 = 8.0;
+const MAX_FONT_SIZE: f32 = 72.0;
+const MIN_BLUR_SIGMA: f32 = 1.0;
+const MAX_BLUR_SIGMA: f32 = 40.0;
+
+/// The range of a magnifier's zoom.
+const MIN_MAGNIFIER_ZOOM: f32 = 1.0;
+const MAX_MAGNIFIER_ZOOM: f32 = 16.0;
+
+/// Screen-space distance within which clicking a polygon's first vertex
+/// closes it.
+const CLOSE_POLYGON_RADIUS: f32 = 8.0;
+
+/// The resize handles of an annotation and their image-space positions.
+fn annotation_handles(kind: &AnnotationKind) -> Vec<(Handle, egui::Pos2)> {
+    match kind {
+        AnnotationKind::Arrow {
+            start, end, control, ..
+        } => {
+            let bend = curve_midpoint(*start, *control, *end);
+            vec![
+                (Handle::ArrowStart, egui::pos2(start.0, start.1)),
+                (Handle::ArrowEnd, egui::pos2(end.0, end.1)),
+                (Handle::Bend, egui::pos2(bend.0, bend.1)),
+            ]
+        }
+        AnnotationKind::Dimension { start, end, .. } => vec![
+            (Handle::ArrowStart, egui::pos2(start.0, start.1)),
+            (Handle::ArrowEnd, egui::pos2(end.0, end.1)),
+        ],
+        AnnotationKind::Rectangle { min, max, .. } | AnnotationKind::Oval { min, max, .. } => {
+            box_handles(*min, *max)
+        }
+        AnnotationKind::Polygon { points, .. } | AnnotationKind::Polyline { points, .. } => points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (Handle::Vertex(i), egui::pos2(p.0, p.1)))
+            .collect(),
+        AnnotationKind::Callout {
+            min,
+            max,
+            anchor,
+            content,
+            font_size,
+            ..
+        } => {
+            let layout = callout_layout(*min, *max, content, *font_size);
+            let mut handles = box_handles(layout.min, layout.max);
+            handles.push((Handle::Anchor, egui::pos2(anchor.0, anchor.1)));
+            handles
+        }
+        AnnotationKind::Magnifier {
+            source_min,
+            source_max,
+            frame,
+            zoom,
+            ..
+        } => {
+            let (min, max) = magnifier_frame(*source_min, *source_max, *frame, *zoom);
+            let mut handles = box_handles(*source_min, *source_max);
+            handles.push((Handle::Frame, egui::pos2(min.0, min.1)));
+            handles.push((Handle::Zoom, egui::pos2(max.0, max.1)));
+            handles
+        }
+        AnnotationKind::Text { .. } | AnnotationKind::Path { .. } | AnnotationKind::Step { .. } => {
+            Vec::new()
+        }
+    }
+}
+
+/// The corner and edge-midpoint handles of a box.
+fn box_handles(min: (f32, f32), max: (f32, f32)) -> Vec<(Handle, egui::Pos2)> {
+    let coord = |edge: Option<BoxEdge>, lo: f32, hi: f32| match edge {
+        Some(BoxEdge::Min) => lo,
+        Some(BoxEdge::Max) => hi,
+        None => (lo + hi) * 0.5,
+    };
+    let edges = [Some(BoxEdge::Min), None, Some(BoxEdge::Max)];
+    edges
+        .iter()
+        .flat_map(|&x| edges.iter().map(move |&y| (x, y)))
+        .filter(|(x, y)| x.is_some() || y.is_some())
+        .map(|(x, y)| {
+            let pos = egui::pos2(coord(x, min.0, max.0), coord(y, min.1, max.1));
+            (Handle::Box { x, y }, pos)
+        })
+        .collect()
+}
+
+fn handle_cursor(handle: Handle) -> egui::CursorIcon {
+    match handle {
+        Handle::ArrowStart | Handle::ArrowEnd | Handle::Vertex(_) | Handle::Anchor => {
+            egui::CursorIcon::Crosshair
+        }
+        Handle::Frame | Handle::Bend => egui::CursorIcon::Move,
+        Handle::Zoom => egui::CursorIcon::ResizeNwSe,
+        Handle::Box { x: Some(_), y: None } => egui::CursorIcon::ResizeHorizontal,
+        Handle::Box { x: None, y: Some(_) } => egui::CursorIcon::ResizeVertical,
+        Handle::Box { x, y } if x == y => egui::CursorIcon::ResizeNwSe,
+        Handle::Box { .. } => egui::CursorIcon::ResizeNeSw,
+    }
+}
+
+/// Load the sidecar for the GUI, turning a failure into a banner instead of
+/// an empty annotation list. Unparseable files are quarantined first.
+fn read_sidecar(image_path: &Path) -> (AnnotationFile, Option<SidecarProblem>) {
+    match load_annotations(image_path) {
+        Ok(file) => (file, None),
+        Err(e) => {
+            let mut error = e.to_string();
+            let quarantined = match &e {
+                LoadError::Parse(..) => match quarantine_sidecar(image_path) {
+                    Ok(copy) => Some(copy),
+                    Err(qe) => {
+                        error.push_str(&format!(" (and could not copy it aside: {qe})"));
+                        None
+                    }
+                },
+                LoadError::Io(..) | LoadError::TooNew(..) => None,
+            };
+            eprintln!("Failed to load annotations: {error}");
+            let overwritable = !matches!(e, LoadError::TooNew(..));
+            (
+                AnnotationFile::default(),
+                Some(SidecarProblem::Load {
+                    error,
+                    quarantined,
+                    overwritable,
+                }),
+            )
+        }
+    }
+}
+
+impl AnnotateApp {
+    fn new(image_path: Option<PathBuf>) -> Self {
+        let (file, sidecar_problem) = image_path
+            .as_deref()
+            .map(read_sidecar)
+            .unwrap_or_default();
+        let raw_image = image_path.as_ref().and_then(|path| image::open(path).ok());
+        let image_size = raw_image
+            .as_ref()
+            .map(|img| (img.width() as f32, img.height() as f32))
+            .unwrap_or((800.0, 600.0));
+
+        Self {
+            image_path,
+            texture: None,
+            source: None,
+            image_size,
+            raw_image,
+            annotations: file.annotations,
+            calibration: file.calibration,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            tool: Tool::Arrow,
+            color: [1.0, 0.0, 0.0],
+            thickness: 3.0,
+            font_size: 20.0,
+            fill_mode: FillMode::None,
+            fill_color: [1.0, 1.0, 0.0],
+            blur_sigma: 8.0,
+            pixelate_block: 12,
+            spotlight_dim: DEFAULT_SPOTLIGHT_DIM,
+            spotlight_grayscale: false,
+            magnify: 2.0,
+            magnifier_connector: true,
+            stroke_style: StrokeStyle::Solid,
+            arrow_heads: ArrowHeads::End,
+            head_shape: HeadShape::Triangle,
+            scale_length: 1.0,
+            scale_unit: "cm".to_string(),
+            drag: DragState::None,
+            selected: BTreeSet::new(),
+            sidecar_problem,
+            backup_failing: false,
+            property_edit_open: false,
+            text_input_pos: None,
+            text_input_buf: String::new(),
+            text_editing: None,
+            callout_draft: None,
+            vertices: Vec::new(),
+            pan: egui::Vec2::ZERO,
+            zoom: 1.0,
+            panning: false,
+            clipboard: None,
+            image_paste_tried: false,
+        }
+    }
+
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new().map_err(|e| e.to_string())?);
+        }
+        Ok(self.clipboard.as_mut().expect("clipboard was just opened"))
+    }
+
+    /// Turn an empty session into one editing the image on the clipboard.
+    fn paste_image(&mut self, ctx: &egui::Context) {
+        let saved = self
+            .clipboard()
+            .and_then(clipboard_image)
+            .and_then(|img| save_clipboard_image(&img));
+        match saved {
+            Ok(path) => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Title(window_title(Some(&path))));
+                *self = Self {
+                    clipboard: self.clipboard.take(),
+                    ..Self::new(Some(path))
+                };
+            }
+            Err(e) => eprintln!("Failed to paste image: {e}"),
+        }
+    }
+
+    /// Put the flattened annotated image on the clipboard, which pastes as
+    /// a PNG.
+    fn copy_export_to_clipboard(&mut self) {
+        let Some(ref raw) = self.raw_image else {
+            return;
+        };
+        let result = render_annotated(raw, &self.annotations, self.calibration.as_ref()).and_then(|img| {
+            let data = arboard::ImageData {
+                width: img.width() as usize,
+                height: img.height() as usize,
+                bytes: std::borrow::Cow::Owned(img.into_raw()),
+            };
+            self.clipboard()?.set_image(data).map_err(|e| e.to_string())
+        });
+        match result {
+            Ok(()) => eprintln!("Copied to clipboard"),
+            Err(e) => eprintln!("Failed to copy to clipboard: {e}"),
+        }
+    }
+
+    fn current_color4(&self) -> Color4 {
         Color4 {
             r: self.color[0],
             g: self.color[1],
@@ -240,32 +1325,398 @@ impl AnnotateApp {
                 a: 1.0,
             }),
             FillMode::Blur => Fill::Blur(self.blur_sigma),
+            FillMode::Pixelate => Fill::Pixelate(self.pixelate_block),
+            FillMode::Redact => Fill::Redact,
+            FillMode::Spotlight => Fill::Spotlight {
+                dim: self.spotlight_dim,
+                grayscale: self.spotlight_grayscale,
+            },
+        }
+    }
+
+    /// The shape the current drawing tool makes when dragged between two
+    /// image-space points, used for both the live preview and the result.
+    fn new_shape(&self, start: egui::Pos2, end: egui::Pos2) -> Option<AnnotationKind> {
+        match self.tool {
+            Tool::Arrow => Some(AnnotationKind::Arrow {
+                start: (start.x, start.y),
+                end: (end.x, end.y),
+                color: self.current_color4(),
+                thickness: self.thickness,
+                style: self.stroke_style,
+                heads: self.arrow_heads,
+                head_shape: self.head_shape,
+                control: None,
+            }),
+            Tool::Rectangle => Some(AnnotationKind::Rectangle {
+                min: (start.x, start.y),
+                max: (end.x, end.y),
+                color: self.current_color4(),
+                thickness: self.thickness,
+                fill: self.current_fill(),
+                style: self.stroke_style,
+            }),
+            Tool::Oval => Some(AnnotationKind::Oval {
+                min: (start.x, start.y),
+                max: (end.x, end.y),
+                color: self.current_color4(),
+                thickness: self.thickness,
+                fill: self.current_fill(),
+                style: self.stroke_style,
+            }),
+            Tool::Magnifier => {
+                // The frame goes to the right of the source, or to its
+                // left when only that side has room for it.
+                let (min, max) = (start.min(end), start.max(end));
+                let width = (max.x - min.x) * self.magnify;
+                let gap = self.thickness * 4.0 + 8.0;
+                let frame_x = if max.x + gap + width > self.image_size.0 && min.x - gap - width >= 0.0 {
+                    min.x - gap - width
+                } else {
+                    max.x + gap
+                };
+                Some(AnnotationKind::Magnifier {
+                    source_min: (min.x, min.y),
+                    source_max: (max.x, max.y),
+                    frame: (frame_x, min.y),
+                    zoom: self.magnify,
+                    color: self.current_color4(),
+                    thickness: self.thickness,
+                    connector: self.magnifier_connector,
+                })
+            }
+            Tool::Dimension => Some(AnnotationKind::Dimension {
+                start: (start.x, start.y),
+                end: (end.x, end.y),
+                color: self.current_color4(),
+                thickness: self.thickness,
+                font_size: self.font_size,
+            }),
+            Tool::Callout => {
+                let (min, max) = (start.min(end), start.max(end));
+                Some(AnnotationKind::Callout {
+                    min: (min.x, min.y),
+                    max: (max.x, max.y),
+                    anchor: (min.x + (max.x - min.x) * 0.25, max.y + self.font_size * 2.0),
+                    content: String::new(),
+                    font_size: self.font_size,
+                    background: self.current_color4(),
+                })
+            }
+            Tool::Text
+            | Tool::Pen
+            | Tool::Highlighter
+            | Tool::Polygon
+            | Tool::Polyline
+            | Tool::Step
+            | Tool::Select => None,
+        }
+    }
+
+    /// The shape the Polygon or Polyline tool makes through image-space
+    /// `points`, or `None` while there are too few of them.
+    fn new_poly(&self, points: &[egui::Pos2]) -> Option<AnnotationKind> {
+        let tuples = points.iter().map(|p| (p.x, p.y)).collect();
+        match self.tool {
+            Tool::Polygon if points.len() >= 3 => Some(AnnotationKind::Polygon {
+                points: tuples,
+                color: self.current_color4(),
+                thickness: self.thickness,
+                fill: self.current_fill(),
+            }),
+            Tool::Polyline if points.len() >= 2 => Some(AnnotationKind::Polyline {
+                points: tuples,
+                color: self.current_color4(),
+                thickness: self.thickness,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Turns the placed vertices into an annotation, if there are enough.
+    fn finish_poly(&mut self) {
+        let mut points = std::mem::take(&mut self.vertices);
+        points.dedup_by(|a, b| a.distance(*b) < 0.5);
+        if let Some(kind) = self.new_poly(&points) {
+            self.push_undo();
+            self.annotations.push(Annotation { kind });
+            self.auto_save();
+        }
+    }
+
+    /// The stroke the Pen or Highlighter makes along image-space `points`.
+    fn new_path(&self, points: &[egui::Pos2]) -> Option<AnnotationKind> {
+        let highlight = match self.tool {
+            Tool::Pen => false,
+            Tool::Highlighter => true,
+            _ => return None,
+        };
+        let mut color = self.current_color4();
+        let mut thickness = self.thickness;
+        if highlight {
+            color.a = HIGHLIGHTER_ALPHA;
+            thickness *= HIGHLIGHTER_WIDTH_SCALE;
         }
+        Some(AnnotationKind::Path {
+            points: points.iter().map(|p| (p.x, p.y)).collect(),
+            color,
+            thickness,
+            highlight,
+        })
     }
 
     fn push_undo(&mut self) {
+        self.cancel_text_edit();
         self.undo_stack.push(self.annotations.clone());
         self.redo_stack.clear();
     }
 
+    /// Close the input re-editing an existing annotation's text, dropping
+    /// what was typed. Anything that changes the annotation list calls
+    /// this, since it could move the annotation away from `text_editing`.
+    fn cancel_text_edit(&mut self) {
+        if self.text_editing.take().is_some() {
+            self.text_input_pos = None;
+            self.text_input_buf.clear();
+        }
+    }
+
     fn undo(&mut self) {
         if let Some(prev) = self.undo_stack.pop() {
+            self.cancel_text_edit();
             self.redo_stack.push(self.annotations.clone());
             self.annotations = prev;
+            let len = self.annotations.len();
+            self.selected.retain(|&i| i < len);
             self.auto_save();
         }
     }
 
     fn redo(&mut self) {
         if let Some(next) = self.redo_stack.pop() {
+            self.cancel_text_edit();
             self.undo_stack.push(self.annotations.clone());
             self.annotations = next;
+            let len = self.annotations.len();
+            self.selected.retain(|&i| i < len);
             self.auto_save();
         }
     }
 
-    fn auto_save(&self) {
-        save_annotations(&self.image_path, &self.annotations);
+    /// The selected annotations, when the Select tool is active. Toolbar
+    /// controls edit these instead of the defaults for new shapes.
+    fn selected_kinds(&self) -> Vec<AnnotationKind> {
+        if self.tool != Tool::Select {
+            return Vec::new();
+        }
+        self.selected
+            .iter()
+            .filter_map(|&i| self.annotations.get(i))
+            .map(|ann| ann.kind.clone())
+            .collect()
+    }
+
+    /// The selected annotation when exactly one is selected.
+    fn single_selection(&self) -> Option<usize> {
+        match self.selected.len() {
+            1 => self.selected.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Apply a toolbar edit to every selected annotation and save them.
+    fn edit_selected(&mut self, edit: impl Fn(&mut AnnotationKind)) {
+        if !self.selected.iter().any(|&i| i < self.annotations.len()) {
+            return;
+        }
+        if !self.property_edit_open {
+            self.push_undo();
+            self.property_edit_open = true;
+        }
+        for &i in &self.selected {
+            if let Some(ann) = self.annotations.get_mut(i) {
+                edit(&mut ann.kind);
+            }
+        }
+        self.auto_save();
+    }
+
+    /// Remove the selected annotations as one undo step.
+    fn delete_selected(&mut self) {
+        let doomed: Vec<usize> = self
+            .selected
+            .iter()
+            .copied()
+            .filter(|&i| i < self.annotations.len())
+            .collect();
+        if doomed.is_empty() {
+            return;
+        }
+        self.push_undo();
+        for &i in doomed.iter().rev() {
+            self.annotations.remove(i);
+        }
+        renumber_steps(&mut self.annotations, None);
+        self.selected.clear();
+        self.auto_save();
+    }
+
+    /// The selected annotations as `AnnotationFile` JSON, the same format as
+    /// the sidecar, so they can be pasted into any image's session.
+    fn copy_selection(&self) -> Option<String> {
+        let annotations: Vec<Annotation> = self
+            .selected
+            .iter()
+            .filter_map(|&i| self.annotations.get(i))
+            .cloned()
+            .collect();
+        if annotations.is_empty() {
+            return None;
+        }
+        let file = AnnotationFile {
+            version: ANNOTZ_VERSION,
+            annotations,
+            calibration: None,
+        };
+        serde_json::to_string_pretty(&file).ok()
+    }
+
+    /// Add annotations shifted by `offset` (image space) as one undo step
+    /// and select them.
+    fn insert_annotations(&mut self, mut annotations: Vec<Annotation>, offset: egui::Vec2) {
+        if annotations.is_empty() {
+            return;
+        }
+        number_added_steps(&self.annotations, &mut annotations);
+        self.push_undo();
+        let first = self.annotations.len();
+        self.annotations.extend(annotations);
+        let added = first..self.annotations.len();
+        for i in added.clone() {
+            self.move_annotation(i, offset);
+        }
+        self.selected = added.collect();
+        self.tool = Tool::Select;
+        self.auto_save();
+    }
+
+    /// Paste annotations copied from this or another image's session. They
+    /// keep their coordinates, so callouts line up across similar images.
+    fn paste_annotations(&mut self, text: &str) {
+        match parse_annotation_file(text) {
+            Ok(file) => self.insert_annotations(file.annotations, egui::Vec2::ZERO),
+            Err(SchemaError::TooNew(version)) => eprintln!(
+                "Cannot paste annotations from a newer annotate-edit (format version {version})"
+            ),
+            // Ordinary text on the clipboard isn't meant for us.
+            Err(SchemaError::Invalid(_)) => {}
+        }
+    }
+
+    fn duplicate_selected(&mut self) {
+        let copies: Vec<Annotation> = self
+            .selected
+            .iter()
+            .filter_map(|&i| self.annotations.get(i))
+            .cloned()
+            .collect();
+        self.insert_annotations(copies, egui::Vec2::splat(DUPLICATE_OFFSET));
+    }
+
+    /// Update the selection for a press at `screen_pos`. With `toggle`
+    /// (shift held) the annotation under the pointer is added or removed;
+    /// otherwise it replaces the selection, unless `keep_group` is set and
+    /// it is already part of it, so a drag moves the whole group. Returns
+    /// the annotation under the pointer.
+    fn select_at(
+        &mut self,
+        canvas_rect: egui::Rect,
+        screen_pos: egui::Pos2,
+        toggle: bool,
+        keep_group: bool,
+    ) -> Option<usize> {
+        let hit = self.hit_test(canvas_rect, screen_pos);
+        match hit {
+            Some(idx) if toggle => {
+                if !self.selected.remove(&idx) {
+                    self.selected.insert(idx);
+                }
+            }
+            Some(idx) if keep_group && self.selected.contains(&idx) => {}
+            Some(idx) => self.selected = BTreeSet::from([idx]),
+            None if toggle => {}
+            None => self.selected.clear(),
+        }
+        hit
+    }
+
+    /// Finish re-editing a Text annotation; clearing its text deletes it.
+    fn commit_text_edit(&mut self, index: usize, new_content: String) {
+        let unchanged = match self.annotations.get_mut(index).and_then(|a| a.kind.content_mut()) {
+            Some(content) => *content == new_content,
+            None => return,
+        };
+        if unchanged {
+            return;
+        }
+        self.push_undo();
+        if new_content.is_empty() {
+            self.annotations.remove(index);
+            self.selected.clear();
+        } else if let Some(content) = self.annotations[index].kind.content_mut() {
+            *content = new_content;
+        }
+        self.auto_save();
+    }
+
+    /// Write the sidecar, unless a failed load has paused saving.
+    fn auto_save(&mut self) {
+        if matches!(self.sidecar_problem, Some(SidecarProblem::Load { .. })) {
+            return;
+        }
+        let Some(image_path) = &self.image_path else {
+            return;
+        };
+        self.sidecar_problem = match save_annotations(image_path, &self.annotations, self.calibration.as_ref()) {
+            Err(e) => Some(SidecarProblem::Save(e.to_string())),
+            Ok(backup_error) => {
+                let already_failing = std::mem::replace(&mut self.backup_failing, backup_error.is_some());
+                match backup_error {
+                    // Keep the notice up, or dismissed, while backups keep failing.
+                    Some(_) if already_failing => self
+                        .sidecar_problem
+                        .take()
+                        .filter(|problem| matches!(problem, SidecarProblem::Backup(_))),
+                    Some(error) => Some(SidecarProblem::Backup(error)),
+                    None => None,
+                }
+            }
+        };
+    }
+
+    fn handle_sidecar_action(&mut self, action: SidecarAction) {
+        match action {
+            SidecarAction::Reload => {
+                let Some(image_path) = &self.image_path else {
+                    return;
+                };
+                let (file, problem) = read_sidecar(image_path);
+                if problem.is_none() {
+                    // Keep in-session edits reachable through undo.
+                    self.push_undo();
+                    self.annotations = file.annotations;
+                    self.calibration = file.calibration;
+                    self.selected.clear();
+                }
+                self.sidecar_problem = problem;
+            }
+            SidecarAction::Overwrite => {
+                self.sidecar_problem = None;
+                self.auto_save();
+            }
+            SidecarAction::Retry => self.auto_save(),
+            SidecarAction::Dismiss => self.sidecar_problem = None,
+        }
     }
 
     /// Convert image-space coords to screen-space
@@ -314,10 +1765,161 @@ impl AnnotateApp {
         }
     }
 
-    /// Draws a live-blurred patch of the source image within the given
-    /// image-space bounds. The blur is computed from the original image only
-    /// (not from other annotations drawn on top), same as text annotations,
-    /// this is a GUI-only approximation of the exported result.
+    /// Keeps `source` in step with the annotations, redrawing the image
+    /// only when its redactions or spotlights change, and not while the
+    /// pointer is down: dragging one would redraw it every frame. Until
+    /// then the canvas shows `paint_spotlight_preview`.
+    fn ensure_source(&mut self, ctx: &egui::Context) {
+        let wanted = SourceKey::new(&self.annotations);
+        if self.source.as_ref().map(|source| &source.key) == Some(&wanted)
+            || ctx.input(|i| i.pointer.any_down())
+        {
+            return;
+        }
+        let Some(raw) = &self.raw_image else {
+            return;
+        };
+        let (image, texture) = if wanted.is_plain() {
+            (Some(raw.to_rgba8()), None)
+        } else {
+            match export_source(raw, &self.annotations) {
+                Ok(rgba) => {
+                    let size = [rgba.width() as usize, rgba.height() as usize];
+                    let color_image =
+                        egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_flat_samples().as_slice());
+                    let texture = ctx.load_texture("source", color_image, egui::TextureOptions::LINEAR);
+                    (Some(rgba), Some(texture))
+                }
+                Err(e) => {
+                    eprintln!("Failed to redact the image: {e}");
+                    (None, None)
+                }
+            }
+        };
+        self.source = Some(CanvasSource {
+            key: wanted,
+            image,
+            texture,
+        });
+    }
+
+    /// Whether `source` shows the image as the annotations want it.
+    fn source_is_current(&self) -> bool {
+        self.source.as_ref().is_some_and(|source| source.key == SourceKey::new(&self.annotations))
+    }
+
+    /// The image, and its texture, that blurs and insets sample on the
+    /// canvas: `source`, as exports draw it. While `source` lags behind, it
+    /// still serves as long as it hides everything the annotations redact.
+    /// Otherwise, or when redacting failed, there is none, so that nothing
+    /// on the canvas shows pixels a redaction hides.
+    fn sampled_source(&self) -> Option<(&RgbaImage, &egui::TextureHandle)> {
+        let source = self.source.as_ref()?;
+        let wanted = redaction_regions(&self.annotations);
+        if !wanted.is_empty() && wanted != source.key.redactions {
+            return None;
+        }
+        Some((source.image.as_ref()?, source.texture.as_ref().or(self.texture.as_ref())?))
+    }
+
+    /// Strokes screen-space `points` multiply-blended over the image, the
+    /// way exports draw highlighters. egui can't blend that way, so the
+    /// stroke is instead a mesh of the image under it, darkened by what
+    /// multiplying the stroke color would leave of each channel. It
+    /// multiplies the image only, not the annotations beneath it.
+    fn paint_multiplied_line(
+        &self,
+        ctx: &egui::Context,
+        painter: &egui::Painter,
+        canvas_rect: egui::Rect,
+        texture: &egui::TextureHandle,
+        points: Vec<egui::Pos2>,
+        stroke: &PrimStroke,
+    ) {
+        let tint = multiply_tint(&stroke.color);
+        let mut tessellator =
+            egui::epaint::Tessellator::new(ctx.pixels_per_point(), Default::default(), [1, 1], Vec::new());
+        let mut mesh = egui::Mesh::with_texture(texture.id());
+        tessellator.tessellate_shape(
+            egui::Shape::line(points, egui::Stroke::new(stroke.width * self.zoom, tint)),
+            &mut mesh,
+        );
+        let (w, h) = self.image_size;
+        for vertex in &mut mesh.vertices {
+            let pos = self.screen_to_image(canvas_rect, vertex.pos);
+            vertex.uv = egui::pos2(pos.x / w, pos.y / h);
+        }
+        painter.add(mesh);
+    }
+
+    /// Fills an image-space outline with a flat color, standing in for a
+    /// fill whose pixels the canvas can't show yet.
+    fn paint_placeholder(
+        &self,
+        painter: &egui::Painter,
+        canvas_rect: egui::Rect,
+        outline: &[(f32, f32)],
+        color: egui::Color32,
+    ) {
+        let mut mesh = egui::Mesh::default();
+        let screen: Vec<egui::Pos2> = outline
+            .iter()
+            .map(|p| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1)))
+            .collect();
+        for pos in &screen {
+            mesh.colored_vertex(*pos, color);
+        }
+        for [a, b, c] in triangulate(&screen) {
+            mesh.add_triangle(a, b, c);
+        }
+        painter.add(mesh);
+    }
+
+    /// Draws the image dimmed outside the spotlight's holes from GPU
+    /// shapes: the whole image tinted darker, then each hole as a mesh
+    /// textured with the undimmed image. Grayscale only shows once
+    /// `ensure_source` has redrawn the texture.
+    fn paint_spotlight_preview(
+        &self,
+        painter: &egui::Painter,
+        canvas_rect: egui::Rect,
+        texture: &egui::TextureHandle,
+        spot: &Spotlight,
+    ) {
+        let img_rect = self.image_rect_on_screen(canvas_rect);
+        let keep = ((1.0 - spot.dim.clamp(0.0, 1.0)) * 255.0).round() as u8;
+        painter.image(
+            texture.id(),
+            img_rect,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::from_gray(keep),
+        );
+        let painter = painter.with_clip_rect(painter.clip_rect().intersect(img_rect));
+        let (w, h) = self.image_size;
+        for hole in &spot.holes {
+            let outline = fill_mask_outline(hole.min, hole.max, &hole.mask);
+            let screen: Vec<egui::Pos2> = outline
+                .iter()
+                .map(|p| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1)))
+                .collect();
+            let mut mesh = egui::Mesh::with_texture(texture.id());
+            for (p, pos) in outline.iter().zip(&screen) {
+                mesh.vertices.push(egui::epaint::Vertex {
+                    pos: *pos,
+                    uv: egui::pos2(p.0 / w, p.1 / h),
+                    color: egui::Color32::WHITE,
+                });
+            }
+            for [a, b, c] in triangulate(&screen) {
+                mesh.add_triangle(a, b, c);
+            }
+            painter.add(mesh);
+        }
+    }
+
+    /// Draws a live-blurred patch of the image within the given
+    /// image-space bounds. Like exports, the blur samples the image with its
+    /// redactions and spotlights, not the annotations drawn beneath it.
     fn draw_blur_fill(
         &self,
         ctx: &egui::Context,
@@ -325,16 +1927,30 @@ impl AnnotateApp {
         canvas_rect: egui::Rect,
         img_bounds: ((f32, f32), (f32, f32)),
         sigma: f32,
-        oval: bool,
+        mask: &FillMask,
     ) {
-        let Some(ref raw) = self.raw_image else {
-            return;
-        };
         let (min, max) = img_bounds;
-        let Some((ox, oy, patch)) = blurred_patch(raw, min.0, min.1, max.0, max.1, sigma, oval)
-        else {
+        let Some((image, _)) = self.sampled_source() else {
+            let outline = fill_mask_outline(min, max, mask);
+            self.paint_placeholder(painter, canvas_rect, &outline, egui::Color32::from_gray(60));
             return;
         };
+        if let Some((ox, oy, patch)) = blurred_patch(image, min.0, min.1, max.0, max.1, sigma, mask) {
+            self.draw_patch(ctx, painter, canvas_rect, (ox, oy), &patch);
+        }
+    }
+
+    /// Draws an image patch whose top-left corner is at `origin` in image
+    /// space.
+    fn draw_patch(
+        &self,
+        ctx: &egui::Context,
+        painter: &egui::Painter,
+        canvas_rect: egui::Rect,
+        origin: (u32, u32),
+        patch: &RgbaImage,
+    ) {
+        let (ox, oy) = origin;
         let size = [patch.width() as usize, patch.height() as usize];
         let color_image = egui::ColorImage::from_rgba_unmultiplied(size, patch.as_flat_samples().as_slice());
         let tex = ctx.load_texture("blur_patch", color_image, egui::TextureOptions::LINEAR);
@@ -352,124 +1968,291 @@ impl AnnotateApp {
 
     fn draw_annotations(&self, ctx: &egui::Context, painter: &egui::Painter, canvas_rect: egui::Rect) {
         for (i, ann) in self.annotations.iter().enumerate() {
-            let is_selected = self.selected == Some(i);
-            match &ann.kind {
-                AnnotationKind::Arrow {
-                    start,
-                    end,
+            if self.text_editing == Some(i) {
+                // The text input stands in for the text, but a callout
+                // keeps its box.
+                let mut kind = ann.kind.clone();
+                if let Some(content) = kind.content_mut() {
+                    content.clear();
+                }
+                self.paint_prims(ctx, painter, canvas_rect, &annotation_prims(&kind, self.calibration.as_ref()));
+                continue;
+            }
+            let bounds = self.paint_prims(ctx, painter, canvas_rect, &annotation_prims(&ann.kind, self.calibration.as_ref()));
+            if !self.selected.contains(&i) {
+                continue;
+            }
+            self.draw_selection_indicator(painter, bounds);
+            if self.single_selection() == Some(i) {
+                for (_, pos) in annotation_handles(&ann.kind) {
+                    let rect = egui::Rect::from_center_size(
+                        self.image_to_screen(canvas_rect, pos),
+                        egui::Vec2::splat(HANDLE_RADIUS * 2.0),
+                    );
+                    painter.rect(
+                        rect,
+                        1.0,
+                        egui::Color32::WHITE,
+                        egui::Stroke::new(1.5, egui::Color32::from_rgb(0, 120, 255)),
+                        egui::StrokeKind::Middle,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Paints scene primitives onto the canvas, scaling image-space
+    /// geometry, stroke widths and font sizes by the current zoom. Returns
+    /// the screen-space bounds of everything painted.
+    fn paint_prims(
+        &self,
+        ctx: &egui::Context,
+        painter: &egui::Painter,
+        canvas_rect: egui::Rect,
+        prims: &[ScenePrim],
+    ) -> egui::Rect {
+        let to_screen = |p: (f32, f32)| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1));
+        let mut bounds = egui::Rect::NOTHING;
+        for prim in prims {
+            match prim {
+                ScenePrim::Line {
+                    from,
+                    to,
                     color,
-                    thickness,
+                    width,
                 } => {
-                    let s =
-                        self.image_to_screen(canvas_rect, egui::pos2(start.0, start.1));
-                    let e = self.image_to_screen(canvas_rect, egui::pos2(end.0, end.1));
-                    let c = color.to_egui();
-                    let t = thickness * self.zoom;
-                    painter.line_segment([s, e], egui::Stroke::new(t, c));
-                    // arrowhead
-                    let dir = (e - s).normalized();
-                    let head_len = (t * 4.0).max(10.0);
-                    let perp = egui::vec2(-dir.y, dir.x);
-                    let p1 = e - dir * head_len + perp * head_len * 0.4;
-                    let p2 = e - dir * head_len - perp * head_len * 0.4;
-                    painter.add(egui::Shape::convex_polygon(
-                        vec![e, p1, p2],
-                        c,
-                        egui::Stroke::NONE,
-                    ));
-                    if is_selected {
-                        self.draw_selection_indicator(
-                            painter,
-                            egui::Rect::from_two_pos(s, e),
-                        );
+                    let (a, b) = (to_screen(*from), to_screen(*to));
+                    painter.line_segment([a, b], egui::Stroke::new(width * self.zoom, color.to_egui()));
+                    bounds = bounds.union(egui::Rect::from_two_pos(a, b));
+                }
+                ScenePrim::Path {
+                    points,
+                    stroke,
+                    multiply,
+                } => {
+                    let points: Vec<egui::Pos2> = points.iter().map(|p| to_screen(*p)).collect();
+                    bounds = bounds.union(egui::Rect::from_points(&points));
+                    match self.sampled_source().filter(|_| *multiply) {
+                        Some((_, tex)) => {
+                            self.paint_multiplied_line(ctx, painter, canvas_rect, tex, points, stroke);
+                        }
+                        None => {
+                            painter.add(egui::Shape::line(
+                                points,
+                                egui::Stroke::new(stroke.width * self.zoom, stroke.color.to_egui()),
+                            ));
+                        }
                     }
                 }
-                AnnotationKind::Rectangle {
-                    min,
-                    max,
-                    color,
-                    thickness,
+                ScenePrim::Polyline {
+                    points,
+                    closed,
                     fill,
+                    stroke,
                 } => {
-                    let s_min =
-                        self.image_to_screen(canvas_rect, egui::pos2(min.0, min.1));
-                    let s_max =
-                        self.image_to_screen(canvas_rect, egui::pos2(max.0, max.1));
-                    let rect = egui::Rect::from_two_pos(s_min, s_max);
-                    let c = color.to_egui();
-                    let t = thickness * self.zoom;
-                    match fill {
-                        Fill::Blur(sigma) => {
-                            self.draw_blur_fill(ctx, painter, canvas_rect, (*min, *max), *sigma, false);
-                            painter.rect_stroke(rect, 0.0, egui::Stroke::new(t, c), egui::StrokeKind::Middle);
+                    let points: Vec<egui::Pos2> = points.iter().map(|p| to_screen(*p)).collect();
+                    bounds = bounds.union(egui::Rect::from_points(&points));
+                    if let Some(fill) = fill.as_ref().filter(|_| *closed) {
+                        let mut mesh = egui::Mesh::default();
+                        for p in &points {
+                            mesh.colored_vertex(*p, fill.to_egui());
                         }
-                        _ => {
-                            let fill_c = match fill {
-                                Fill::Color(fc) => fc.to_egui(),
-                                _ => egui::Color32::TRANSPARENT,
-                            };
-                            painter.rect(rect, 0.0, fill_c, egui::Stroke::new(t, c), egui::StrokeKind::Middle);
+                        for [a, b, c] in triangulate(&points) {
+                            mesh.add_triangle(a, b, c);
                         }
+                        painter.add(mesh);
                     }
-                    if is_selected {
-                        self.draw_selection_indicator(painter, rect);
-                    }
+                    self.paint_outline(painter, points, *closed, stroke);
                 }
-                AnnotationKind::Oval {
+                ScenePrim::Polygon { points, color } => {
+                    let points: Vec<egui::Pos2> = points.iter().map(|p| to_screen(*p)).collect();
+                    bounds = bounds.union(egui::Rect::from_points(&points));
+                    painter.add(egui::Shape::convex_polygon(
+                        points,
+                        color.to_egui(),
+                        egui::Stroke::NONE,
+                    ));
+                }
+                ScenePrim::Rect {
                     min,
                     max,
-                    color,
-                    thickness,
                     fill,
+                    stroke,
                 } => {
-                    let s_min =
-                        self.image_to_screen(canvas_rect, egui::pos2(min.0, min.1));
-                    let s_max =
-                        self.image_to_screen(canvas_rect, egui::pos2(max.0, max.1));
-                    let center = egui::pos2((s_min.x + s_max.x) * 0.5, (s_min.y + s_max.y) * 0.5);
-                    let radii = egui::vec2((s_max.x - s_min.x).abs() * 0.5, (s_max.y - s_min.y).abs() * 0.5);
-                    let c = color.to_egui();
-                    let t = thickness * self.zoom;
-                    match fill {
-                        Fill::Blur(sigma) => {
-                            self.draw_blur_fill(ctx, painter, canvas_rect, (*min, *max), *sigma, true);
-                            painter.add(egui::epaint::EllipseShape { center, radius: radii, fill: egui::Color32::TRANSPARENT, stroke: egui::Stroke::new(t, c) });
-                        }
-                        _ => {
-                            let fill_c = match fill {
-                                Fill::Color(fc) => fc.to_egui(),
-                                _ => egui::Color32::TRANSPARENT,
-                            };
-                            painter.add(egui::epaint::EllipseShape { center, radius: radii, fill: fill_c, stroke: egui::Stroke::new(t, c) });
-                        }
+                    let rect = egui::Rect::from_two_pos(to_screen(*min), to_screen(*max));
+                    // egui only strokes shapes solid, so dashed outlines
+                    // are drawn on their own.
+                    let dashed = stroke.as_ref().is_some_and(|s| s.style != StrokeStyle::Solid);
+                    painter.rect(
+                        rect,
+                        0.0,
+                        fill.as_ref().map_or(egui::Color32::TRANSPARENT, Color4::to_egui),
+                        if dashed { egui::Stroke::NONE } else { self.egui_stroke(stroke) },
+                        egui::StrokeKind::Middle,
+                    );
+                    if dashed {
+                        let corners = vec![rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom()];
+                        self.paint_outline(painter, corners, true, stroke);
                     }
-                    let bounding = egui::Rect::from_two_pos(s_min, s_max);
-                    if is_selected {
-                        self.draw_selection_indicator(painter, bounding);
+                    bounds = bounds.union(rect);
+                }
+                ScenePrim::Ellipse {
+                    center,
+                    radii,
+                    fill,
+                    stroke,
+                } => {
+                    let center = to_screen(*center);
+                    let radius = egui::vec2(radii.0, radii.1) * self.zoom;
+                    let dashed = stroke.as_ref().is_some_and(|s| s.style != StrokeStyle::Solid);
+                    painter.add(egui::epaint::EllipseShape {
+                        center,
+                        radius,
+                        fill: fill.as_ref().map_or(egui::Color32::TRANSPARENT, Color4::to_egui),
+                        stroke: if dashed { egui::Stroke::NONE } else { self.egui_stroke(stroke) },
+                    });
+                    if dashed {
+                        let outline = (0..ELLIPSE_SEGMENTS)
+                            .map(|i| {
+                                let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                                center + egui::vec2(radius.x * angle.cos(), radius.y * angle.sin())
+                            })
+                            .collect();
+                        self.paint_outline(painter, outline, true, stroke);
                     }
+                    bounds = bounds.union(egui::Rect::from_center_size(center, radius * 2.0));
                 }
-                AnnotationKind::Text {
+                ScenePrim::Blur {
+                    min,
+                    max,
+                    sigma,
+                    mask,
+                } => {
+                    self.draw_blur_fill(ctx, painter, canvas_rect, (*min, *max), *sigma, mask);
+                    bounds = bounds.union(egui::Rect::from_two_pos(to_screen(*min), to_screen(*max)));
+                }
+                ScenePrim::Redaction {
+                    min,
+                    max,
+                    effect,
+                    mask,
+                } => {
+                    // Drawn into `source` by `ensure_source`; until then,
+                    // and for one being drawn, a flat fill covers it.
+                    let region = RedactionRegion {
+                        min: *min,
+                        max: *max,
+                        effect: *effect,
+                        mask: mask.clone(),
+                    };
+                    let baked = self.source_is_current()
+                        && self.source.as_ref().is_some_and(|source| source.key.redactions.contains(&region));
+                    if !baked {
+                        let color = match effect {
+                            Redaction::Pixelate(_) => egui::Color32::from_gray(128),
+                            Redaction::Solid => egui::Color32::BLACK,
+                        };
+                        let outline = fill_mask_outline(*min, *max, mask);
+                        self.paint_placeholder(painter, canvas_rect, &outline, color);
+                    }
+                    bounds = bounds.union(egui::Rect::from_two_pos(to_screen(*min), to_screen(*max)));
+                }
+                // Drawn into the image texture by `ensure_source`.
+                ScenePrim::Spotlight { min, max, .. } => {
+                    bounds = bounds.union(egui::Rect::from_two_pos(to_screen(*min), to_screen(*max)));
+                }
+                ScenePrim::Inset {
+                    src_min,
+                    src_max,
+                    min,
+                    max,
+                } => {
+                    let visible = clip_inset(*src_min, *src_max, *min, *max, self.image_size);
+                    if let Some([s0, s1, d0, d1]) = visible {
+                        let dest = egui::Rect::from_min_max(to_screen(d0), to_screen(d1));
+                        match self.sampled_source() {
+                            Some((_, tex)) => {
+                                let (w, h) = self.image_size;
+                                painter.image(
+                                    tex.id(),
+                                    dest,
+                                    egui::Rect::from_min_max(
+                                        egui::pos2(s0.0 / w, s0.1 / h),
+                                        egui::pos2(s1.0 / w, s1.1 / h),
+                                    ),
+                                    egui::Color32::WHITE,
+                                );
+                            }
+                            // Until the redacted image is ready, or if redacting failed.
+                            None => {
+                                painter.rect_filled(dest, 0.0, egui::Color32::from_gray(60));
+                            }
+                        }
+                    }
+                    bounds = bounds.union(egui::Rect::from_two_pos(to_screen(*min), to_screen(*max)));
+                }
+                ScenePrim::Text {
                     pos,
                     content,
                     font_size,
                     color,
                 } => {
-                    let s = self.image_to_screen(canvas_rect, egui::pos2(pos.0, pos.1));
+                    let s = to_screen(*pos);
                     let c = color.to_egui();
-                    let fs = font_size * self.zoom;
                     let galley = painter.layout_no_wrap(
                         content.clone(),
-                        egui::FontId::proportional(fs),
+                        egui::FontId::proportional(font_size * self.zoom),
                         c,
                     );
-                    let text_rect = egui::Rect::from_min_size(s, galley.size());
+                    bounds = bounds.union(egui::Rect::from_min_size(s, galley.size()));
                     painter.galley(s, galley, c);
-                    if is_selected {
-                        self.draw_selection_indicator(painter, text_rect);
-                    }
                 }
             }
         }
+        bounds
+    }
+
+    fn egui_stroke(&self, stroke: &Option<PrimStroke>) -> egui::Stroke {
+        match stroke {
+            Some(s) => egui::Stroke::new(s.width * self.zoom, s.color.to_egui()),
+            None => egui::Stroke::NONE,
+        }
+    }
+
+    /// Strokes the screen-space outline through `points` in the stroke's
+    /// style.
+    fn paint_outline(
+        &self,
+        painter: &egui::Painter,
+        mut points: Vec<egui::Pos2>,
+        closed: bool,
+        stroke: &Option<PrimStroke>,
+    ) {
+        let Some(prim_stroke) = stroke else {
+            return;
+        };
+        let egui_stroke = self.egui_stroke(stroke);
+        let Some([on, off]) = prim_stroke.style.dash_pattern(prim_stroke.width) else {
+            painter.add(if closed {
+                egui::Shape::closed_line(points, egui_stroke)
+            } else {
+                egui::Shape::line(points, egui_stroke)
+            });
+            return;
+        };
+        if closed {
+            points.extend(points.first().copied());
+        }
+        let shapes = match prim_stroke.style {
+            StrokeStyle::Dotted => egui::Shape::dotted_line(
+                &points,
+                egui_stroke.color,
+                (on + off) * self.zoom,
+                egui_stroke.width * 0.5,
+            ),
+            _ => egui::Shape::dashed_line(&points, egui_stroke, on * self.zoom, off * self.zoom),
+        };
+        painter.extend(shapes);
     }
 
     fn draw_selection_indicator(&self, painter: &egui::Painter, rect: egui::Rect) {
@@ -492,15 +2275,59 @@ impl AnnotateApp {
                 AnnotationKind::Arrow {
                     start,
                     end,
+                    control,
                     thickness,
                     ..
                 } => {
-                    let s =
-                        self.image_to_screen(canvas_rect, egui::pos2(start.0, start.1));
-                    let e =
-                        self.image_to_screen(canvas_rect, egui::pos2(end.0, end.1));
-                    point_to_segment_dist(screen_pos, s, e)
+                    let points: Vec<egui::Pos2> = arrow_curve(*start, *control, *end)
+                        .into_iter()
+                        .map(|p| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1)))
+                        .collect();
+                    points
+                        .windows(2)
+                        .map(|pair| point_to_segment_dist(screen_pos, pair[0], pair[1]))
+                        .fold(f32::INFINITY, f32::min)
+                        < (thickness * self.zoom + 8.0)
+                }
+                AnnotationKind::Magnifier {
+                    source_min,
+                    source_max,
+                    frame,
+                    zoom,
+                    thickness,
+                    ..
+                } => {
+                    let to_screen =
+                        |p: (f32, f32)| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1));
+                    let (min, max) = magnifier_frame(*source_min, *source_max, *frame, *zoom);
+                    let frame = egui::Rect::from_two_pos(to_screen(min), to_screen(max));
+                    let source = egui::Rect::from_two_pos(to_screen(*source_min), to_screen(*source_max));
+                    let margin = thickness * self.zoom + 8.0;
+                    frame.expand(thickness * self.zoom + 4.0).contains(screen_pos)
+                        || (source.expand(margin).contains(screen_pos)
+                            && !source.shrink(margin).contains(screen_pos))
+                }
+                AnnotationKind::Dimension {
+                    start,
+                    end,
+                    thickness,
+                    font_size,
+                    ..
+                } => {
+                    let to_screen =
+                        |p: (f32, f32)| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1));
+                    let layout = dimension_layout(
+                        *start,
+                        *end,
+                        *thickness,
+                        *font_size,
+                        self.calibration.as_ref(),
+                    );
+                    let label =
+                        egui::Rect::from_two_pos(to_screen(layout.backdrop_min), to_screen(layout.backdrop_max));
+                    point_to_segment_dist(screen_pos, to_screen(*start), to_screen(*end))
                         < (thickness * self.zoom + 8.0)
+                        || label.contains(screen_pos)
                 }
                 AnnotationKind::Rectangle {
                     min,
@@ -554,6 +2381,32 @@ impl AnnotateApp {
                         outside_inner && inside_outer
                     }
                 }
+                AnnotationKind::Callout {
+                    min,
+                    max,
+                    anchor,
+                    content,
+                    font_size,
+                    ..
+                } => {
+                    let layout = callout_layout(*min, *max, content, *font_size);
+                    let outline: Vec<egui::Pos2> =
+                        callout_outline(layout.min, layout.max, *anchor, *font_size)
+                            .iter()
+                            .map(|p| egui::pos2(p.0, p.1))
+                            .collect();
+                    point_in_polygon(self.screen_to_image(canvas_rect, screen_pos), &outline)
+                }
+                AnnotationKind::Step {
+                    pos,
+                    number,
+                    font_size,
+                    ..
+                } => {
+                    let center = self.image_to_screen(canvas_rect, egui::pos2(pos.0, pos.1));
+                    let badge = step_badge(*pos, *number, *font_size);
+                    center.distance(screen_pos) <= badge.radius * self.zoom
+                }
                 AnnotationKind::Text {
                     pos,
                     content,
@@ -570,6 +2423,49 @@ impl AnnotateApp {
                     );
                     rect.expand(4.0).contains(screen_pos)
                 }
+                AnnotationKind::Path {
+                    points, thickness, ..
+                } => {
+                    let points: Vec<egui::Pos2> = points
+                        .iter()
+                        .map(|p| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1)))
+                        .collect();
+                    let slack = thickness * self.zoom * 0.5 + 6.0;
+                    match points.as_slice() {
+                        [only] => (*only - screen_pos).length() < slack,
+                        _ => points
+                            .windows(2)
+                            .any(|w| point_to_segment_dist(screen_pos, w[0], w[1]) < slack),
+                    }
+                }
+                AnnotationKind::Polygon {
+                    points,
+                    thickness,
+                    fill,
+                    ..
+                } => {
+                    let points: Vec<egui::Pos2> = points
+                        .iter()
+                        .map(|p| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1)))
+                        .collect();
+                    let slack = thickness * self.zoom + 8.0;
+                    let on_edge = (0..points.len()).any(|i| {
+                        let next = points[(i + 1) % points.len()];
+                        point_to_segment_dist(screen_pos, points[i], next) < slack
+                    });
+                    on_edge || (!matches!(fill, Fill::None) && point_in_polygon(screen_pos, &points))
+                }
+                AnnotationKind::Polyline {
+                    points, thickness, ..
+                } => {
+                    let points: Vec<egui::Pos2> = points
+                        .iter()
+                        .map(|p| self.image_to_screen(canvas_rect, egui::pos2(p.0, p.1)))
+                        .collect();
+                    points.windows(2).any(|w| {
+                        point_to_segment_dist(screen_pos, w[0], w[1]) < thickness * self.zoom + 8.0
+                    })
+                }
             };
             if hit {
                 return Some(i);
@@ -578,10 +2474,157 @@ impl AnnotateApp {
         None
     }
 
+    /// The handle of the selected annotation under `screen_pos`, if any.
+    /// Handles sit on top of every annotation, so this is checked before
+    /// `hit_test`.
+    fn handle_hit_test(
+        &self,
+        canvas_rect: egui::Rect,
+        screen_pos: egui::Pos2,
+    ) -> Option<(usize, Handle)> {
+        let index = self.single_selection()?;
+        let ann = self.annotations.get(index)?;
+        annotation_handles(&ann.kind)
+            .into_iter()
+            .find(|(_, pos)| {
+                let s = self.image_to_screen(canvas_rect, *pos);
+                (s.x - screen_pos.x).abs() <= HANDLE_RADIUS + 2.0
+                    && (s.y - screen_pos.y).abs() <= HANDLE_RADIUS + 2.0
+            })
+            .map(|(handle, _)| (index, handle))
+    }
+
+    fn resize_annotation(&mut self, index: usize, handle: Handle, delta_img: egui::Vec2) {
+        let Some(ann) = self.annotations.get_mut(index) else {
+            return;
+        };
+        match (&mut ann.kind, handle) {
+            (
+                AnnotationKind::Arrow { start, .. } | AnnotationKind::Dimension { start, .. },
+                Handle::ArrowStart,
+            ) => {
+                start.0 += delta_img.x;
+                start.1 += delta_img.y;
+            }
+            (
+                AnnotationKind::Arrow { end, .. } | AnnotationKind::Dimension { end, .. },
+                Handle::ArrowEnd,
+            ) => {
+                end.0 += delta_img.x;
+                end.1 += delta_img.y;
+            }
+            // The handle sits halfway along the curve, which moves half as
+            // far as the control point does.
+            (
+                AnnotationKind::Arrow {
+                    start, end, control, ..
+                },
+                Handle::Bend,
+            ) => {
+                let c = control.unwrap_or(((start.0 + end.0) * 0.5, (start.1 + end.1) * 0.5));
+                *control = Some((c.0 + delta_img.x * 2.0, c.1 + delta_img.y * 2.0));
+            }
+            (
+                AnnotationKind::Rectangle { min, max, .. }
+                | AnnotationKind::Oval { min, max, .. }
+                | AnnotationKind::Magnifier {
+                    source_min: min,
+                    source_max: max,
+                    ..
+                },
+                Handle::Box { x, y },
+            ) => {
+                match x {
+                    Some(BoxEdge::Min) => min.0 += delta_img.x,
+                    Some(BoxEdge::Max) => max.0 += delta_img.x,
+                    None => {}
+                }
+                match y {
+                    Some(BoxEdge::Min) => min.1 += delta_img.y,
+                    Some(BoxEdge::Max) => max.1 += delta_img.y,
+                    None => {}
+                }
+            }
+            (
+                AnnotationKind::Callout {
+                    min,
+                    max,
+                    content,
+                    font_size,
+                    ..
+                },
+                Handle::Box { x, y },
+            ) => {
+                // Start from the box as drawn, which may have grown to fit
+                // the text, so the edge follows the pointer right away.
+                let layout = callout_layout(*min, *max, content, *font_size);
+                (*min, *max) = (layout.min, layout.max);
+                match x {
+                    Some(BoxEdge::Min) => min.0 += delta_img.x,
+                    Some(BoxEdge::Max) => max.0 += delta_img.x,
+                    None => {}
+                }
+                match y {
+                    Some(BoxEdge::Min) => min.1 += delta_img.y,
+                    Some(BoxEdge::Max) => max.1 += delta_img.y,
+                    None => {}
+                }
+            }
+            (AnnotationKind::Callout { anchor, .. }, Handle::Anchor) => {
+                anchor.0 += delta_img.x;
+                anchor.1 += delta_img.y;
+            }
+            (AnnotationKind::Magnifier { frame, .. }, Handle::Frame) => {
+                frame.0 += delta_img.x;
+                frame.1 += delta_img.y;
+            }
+            (
+                AnnotationKind::Magnifier {
+                    source_min,
+                    source_max,
+                    frame,
+                    zoom,
+                    ..
+                },
+                Handle::Zoom,
+            ) => {
+                // Keep the frame's shape: take the zoom from whichever
+                // axis the pointer has moved further along.
+                let (_, max) = magnifier_frame(*source_min, *source_max, *frame, *zoom);
+                let source = (
+                    (source_max.0 - source_min.0).abs().max(1.0),
+                    (source_max.1 - source_min.1).abs().max(1.0),
+                );
+                let by_x = (max.0 + delta_img.x - frame.0) / source.0;
+                let by_y = (max.1 + delta_img.y - frame.1) / source.1;
+                let new_zoom = if (by_x - *zoom).abs() > (by_y - *zoom).abs() { by_x } else { by_y };
+                *zoom = new_zoom.clamp(MIN_MAGNIFIER_ZOOM, MAX_MAGNIFIER_ZOOM);
+            }
+            (
+                AnnotationKind::Polygon { points, .. } | AnnotationKind::Polyline { points, .. },
+                Handle::Vertex(i),
+            ) => {
+                if let Some(p) = points.get_mut(i) {
+                    p.0 += delta_img.x;
+                    p.1 += delta_img.y;
+                }
+            }
+            _ => {}
+        }
+    }
+
     fn move_annotation(&mut self, index: usize, delta_img: egui::Vec2) {
         if let Some(ann) = self.annotations.get_mut(index) {
             match &mut ann.kind {
-                AnnotationKind::Arrow { start, end, .. } => {
+                AnnotationKind::Arrow {
+                    start, end, control, ..
+                } => {
+                    for p in [start, end].into_iter().chain(control.as_mut()) {
+                        p.0 += delta_img.x;
+                        p.1 += delta_img.y;
+                    }
+                }
+                AnnotationKind::Dimension { start, end, .. } => {
                     start.0 += delta_img.x;
                     start.1 += delta_img.y;
                     end.0 += delta_img.x;
@@ -594,262 +2637,1400 @@ impl AnnotateApp {
                     max.0 += delta_img.x;
                     max.1 += delta_img.y;
                 }
-                AnnotationKind::Text { pos, .. } => {
-                    pos.0 += delta_img.x;
-                    pos.1 += delta_img.y;
-                }
-            }
-        }
-    }
-
-    fn export_annotated(&self) {
-        let Some(ref raw) = self.raw_image else {
-            return;
-        };
-        let mut img: RgbaImage = raw.to_rgba8();
-
-        for ann in &self.annotations {
-            match &ann.kind {
-                AnnotationKind::Arrow {
-                    start,
-                    end,
-                    color,
-                    thickness,
+                AnnotationKind::Callout {
+                    min, max, anchor, ..
                 } => {
-                    let c = [
-                        (color.r * 255.0) as u8,
-                        (color.g * 255.0) as u8,
-                        (color.b * 255.0) as u8,
-                        (color.a * 255.0) as u8,
-                    ];
-                    draw_line_on_image(
-                        &mut img, start.0, start.1, end.0, end.1, *thickness, c,
-                    );
-                    let dx = end.0 - start.0;
-                    let dy = end.1 - start.1;
-                    let len = (dx * dx + dy * dy).sqrt();
-                    if len > 0.0 {
-                        let dir = (dx / len, dy / len);
-                        let perp = (-dir.1, dir.0);
-                        let head_len = (thickness * 4.0).max(10.0);
-                        let p1 = (
-                            end.0 - dir.0 * head_len + perp.0 * head_len * 0.4,
-                            end.1 - dir.1 * head_len + perp.1 * head_len * 0.4,
-                        );
-                        let p2 = (
-                            end.0 - dir.0 * head_len - perp.0 * head_len * 0.4,
-                            end.1 - dir.1 * head_len - perp.1 * head_len * 0.4,
-                        );
-                        draw_line_on_image(
-                            &mut img, end.0, end.1, p1.0, p1.1, *thickness, c,
-                        );
-                        draw_line_on_image(
-                            &mut img, end.0, end.1, p2.0, p2.1, *thickness, c,
-                        );
-                        draw_line_on_image(
-                            &mut img, p1.0, p1.1, p2.0, p2.1, *thickness, c,
-                        );
+                    for p in [min, max, anchor] {
+                        p.0 += delta_img.x;
+                        p.1 += delta_img.y;
                     }
                 }
-                AnnotationKind::Rectangle {
-                    min,
-                    max,
-                    color,
-                    thickness,
-                    fill,
+                AnnotationKind::Magnifier {
+                    source_min,
+                    source_max,
+                    frame,
+                    ..
                 } => {
-                    let c = [
-                        (color.r * 255.0) as u8,
-                        (color.g * 255.0) as u8,
-                        (color.b * 255.0) as u8,
-                        (color.a * 255.0) as u8,
-                    ];
-                    match fill {
-                        Fill::Color(fc) => {
-                            let fc = [
-                                (fc.r * 255.0) as u8,
-                                (fc.g * 255.0) as u8,
-                                (fc.b * 255.0) as u8,
-                                (fc.a * 255.0) as u8,
-                            ];
-                            fill_rect_on_image(&mut img, min.0, min.1, max.0, max.1, fc);
-                        }
-                        Fill::Blur(sigma) => {
-                            if let Some((ox, oy, patch)) =
-                                blurred_patch(&img, min.0, min.1, max.0, max.1, *sigma, false)
-                            {
-                                imageops::overlay(&mut img, &patch, ox as i64, oy as i64);
-                            }
-                        }
-                        Fill::None => {}
+                    for p in [source_min, source_max, frame] {
+                        p.0 += delta_img.x;
+                        p.1 += delta_img.y;
                     }
-                    draw_line_on_image(
-                        &mut img, min.0, min.1, max.0, min.1, *thickness, c,
-                    );
-                    draw_line_on_image(
-                        &mut img, max.0, min.1, max.0, max.1, *thickness, c,
-                    );
-                    draw_line_on_image(
-                        &mut img, max.0, max.1, min.0, max.1, *thickness, c,
-                    );
-                    draw_line_on_image(
-                        &mut img, min.0, max.1, min.0, min.1, *thickness, c,
-                    );
                 }
-                AnnotationKind::Oval {
-                    min,
-                    max,
-                    color,
-                    thickness,
-                    fill,
-                } => {
-                    let c = [
-                        (color.r * 255.0) as u8,
-                        (color.g * 255.0) as u8,
-                        (color.b * 255.0) as u8,
-                        (color.a * 255.0) as u8,
-                    ];
-                    let cx = (min.0 + max.0) * 0.5;
-                    let cy = (min.1 + max.1) * 0.5;
-                    let rx = (max.0 - min.0).abs() * 0.5;
-                    let ry = (max.1 - min.1).abs() * 0.5;
-                    match fill {
-                        Fill::Color(fc) => {
-                            let fc = [
-                                (fc.r * 255.0) as u8,
-                                (fc.g * 255.0) as u8,
-                                (fc.b * 255.0) as u8,
-                                (fc.a * 255.0) as u8,
-                            ];
-                            fill_oval_on_image(&mut img, cx, cy, rx, ry, fc);
-                        }
-                        Fill::Blur(sigma) => {
-                            if let Some((ox, oy, patch)) =
-                                blurred_patch(&img, min.0, min.1, max.0, max.1, *sigma, true)
-                            {
-                                imageops::overlay(&mut img, &patch, ox as i64, oy as i64);
-                            }
-                        }
-                        Fill::None => {}
-                    }
-                    draw_oval_on_image(&mut img, cx, cy, rx, ry, *thickness, c);
+                AnnotationKind::Text { pos, .. } | AnnotationKind::Step { pos, .. } => {
+                    pos.0 += delta_img.x;
+                    pos.1 += delta_img.y;
                 }
-                AnnotationKind::Text { .. } => {
-                    // Text rendering to image requires a font rasterizer;
-                    // text annotations only appear in the GUI for now.
+                AnnotationKind::Path { points, .. }
+                | AnnotationKind::Polygon { points, .. }
+                | AnnotationKind::Polyline { points, .. } => {
+                    for p in points {
+                        p.0 += delta_img.x;
+                        p.1 += delta_img.y;
+                    }
                 }
             }
         }
+    }
 
-        let out_path = self.image_path.with_file_name(format!(
-            "{}_annotated.png",
-            self.image_path
-                .file_stem()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or("out")
-        ));
-        let _ = img.save(&out_path);
-        eprintln!("Exported to {}", out_path.display());
+    fn export_annotated(&self, format: ExportFormat) {
+        let Some(ref raw) = self.raw_image else {
+            return;
+        };
+        let Some(ref image_path) = self.image_path else {
+            return;
+        };
+        let out_path = annotated_path(image_path, format.extension());
+        let result = render_export(
+            format,
+            raw,
+            &self.annotations,
+            self.calibration.as_ref(),
+            &SvgImageRef::Embedded,
+        )
+        .and_then(|data| write_atomic(&out_path, &data).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => eprintln!("Exported to {}", out_path.display()),
+            Err(e) => eprintln!("Failed to export {}: {e}", out_path.display()),
+        }
     }
 }
 
-fn point_to_segment_dist(p: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
-    let ab = b - a;
-    let ap = p - a;
-    let t = ap.dot(ab) / ab.dot(ab);
-    let t = t.clamp(0.0, 1.0);
-    let closest = a + ab * t;
-    (p - closest).length()
+// ── Scene Rendering ─────────────────────────────────────────────────────────
+//
+// Annotations are lowered into image-space `ScenePrim`s once, and both the
+// canvas (`AnnotateApp::paint_prims`) and the exporter (`rasterize_prims`)
+// draw from that list, so what gets exported is what was on screen.
+
+#[derive(Clone, Debug)]
+struct PrimStroke {
+    width: f32,
+    color: Color4,
+    style: StrokeStyle,
 }
 
-fn draw_line_on_image(
-    img: &mut RgbaImage,
-    x0: f32,
-    y0: f32,
-    x1: f32,
-    y1: f32,
-    thickness: f32,
-    color: [u8; 4],
-) {
-    let dx = x1 - x0;
-    let dy = y1 - y0;
-    let len = (dx * dx + dy * dy).sqrt();
-    let steps = (len * 2.0) as i32;
-    let half_t = (thickness / 2.0).max(0.5) as i32;
-    let (w, h) = (img.width() as i32, img.height() as i32);
+#[derive(Clone, Debug)]
+enum ScenePrim {
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        color: Color4,
+        width: f32,
+    },
+    /// An open stroke with round caps and joins, multiply-blended when
+    /// `multiply` is set.
+    Path {
+        points: Vec<(f32, f32)>,
+        stroke: PrimStroke,
+        multiply: bool,
+    },
+    /// Straight segments through `points`, closed when `closed` and then
+    /// optionally filled. Unlike `Polygon` it may be non-convex.
+    Polyline {
+        points: Vec<(f32, f32)>,
+        closed: bool,
+        fill: Option<Color4>,
+        stroke: Option<PrimStroke>,
+    },
+    /// A filled convex polygon.
+    Polygon {
+        points: Vec<(f32, f32)>,
+        color: Color4,
+    },
+    /// An axis-aligned rectangle, its stroke centered on the edges.
+    Rect {
+        min: (f32, f32),
+        max: (f32, f32),
+        fill: Option<Color4>,
+        stroke: Option<PrimStroke>,
+    },
+    Ellipse {
+        center: (f32, f32),
+        radii: (f32, f32),
+        fill: Option<Color4>,
+        stroke: Option<PrimStroke>,
+    },
+    /// Covers the bounds, clipped to `mask`, with a blur of the image as
+    /// `export_source` makes it. Annotations beneath it are covered, not
+    /// blurred, in every output.
+    Blur {
+        min: (f32, f32),
+        max: (f32, f32),
+        sigma: f32,
+        mask: FillMask,
+    },
+    /// Pixelates or blacks out the source image within the bounds, clipped
+    /// to `mask`. Exports apply these to the source up front, in
+    /// `redact_source`, so only the canvas draws them as prims.
+    Redaction {
+        min: (f32, f32),
+        max: (f32, f32),
+        effect: Redaction,
+        mask: FillMask,
+    },
+    /// A region kept lit while the rest of the image is dimmed. Every
+    /// renderer applies all of an image's spotlights at once, beneath the
+    /// annotations, so this prim only marks where they are.
+    Spotlight {
+        min: (f32, f32),
+        max: (f32, f32),
+        dim: f32,
+        grayscale: bool,
+        mask: FillMask,
+    },
+    /// The source image within `src_min`..`src_max`, scaled to fill
+    /// `min`..`max`.
+    Inset {
+        src_min: (f32, f32),
+        src_max: (f32, f32),
+        min: (f32, f32),
+        max: (f32, f32),
+    },
+    /// Text whose first row has its top-left corner at `pos`.
+    Text {
+        pos: (f32, f32),
+        content: String,
+        font_size: f32,
+        color: Color4,
+    },
+}
 
-    for i in 0..=steps {
-        let t = i as f32 / steps.max(1) as f32;
-        let cx = (x0 + dx * t) as i32;
-        let cy = (y0 + dy * t) as i32;
-        for oy in -half_t..=half_t {
-            for ox in -half_t..=half_t {
-                let px = cx + ox;
-                let py = cy + oy;
-                if px >= 0 && px < w && py >= 0 && py < h {
-                    img.put_pixel(px as u32, py as u32, image::Rgba(color));
-                }
-            }
+/// The part of a filled shape's bounds that its fill covers.
+#[derive(Clone, Debug, PartialEq)]
+enum FillMask {
+    Rect,
+    /// The ellipse inscribed in the bounds.
+    Oval,
+    /// A polygon with image-space vertices.
+    Polygon(Vec<(f32, f32)>),
+}
+
+/// One end of an arrow, as drawn.
+enum ArrowHead {
+    /// A filled triangle, tip first.
+    Triangle(Vec<(f32, f32)>),
+    /// The two sides of the triangle, stroked, with the tip in the middle.
+    Open(Vec<(f32, f32)>),
+    Dot { center: (f32, f32), radius: f32 },
+}
+
+impl ArrowHead {
+    fn prim(self, color: &Color4, thickness: f32) -> ScenePrim {
+        match self {
+            ArrowHead::Triangle(points) => ScenePrim::Polygon {
+                points,
+                color: color.clone(),
+            },
+            ArrowHead::Open(points) => ScenePrim::Polyline {
+                points,
+                closed: false,
+                fill: None,
+                stroke: Some(PrimStroke {
+                    width: thickness,
+                    color: color.clone(),
+                    style: StrokeStyle::Solid,
+                }),
+            },
+            ArrowHead::Dot { center, radius } => ScenePrim::Ellipse {
+                center,
+                radii: (radius, radius),
+                fill: Some(color.clone()),
+                stroke: None,
+            },
         }
     }
 }
 
-fn draw_oval_on_image(
-    img: &mut RgbaImage,
-    cx: f32,
-    cy: f32,
-    rx: f32,
-    ry: f32,
+/// The head at the `tip` of an arrow coming from `from`, sized from the
+/// image-space thickness so it scales with the zoom like the shaft does.
+fn arrow_head(from: (f32, f32), tip: (f32, f32), thickness: f32, shape: HeadShape) -> Option<ArrowHead> {
+    let dx = tip.0 - from.0;
+    let dy = tip.1 - from.1;
+    let len = (dx * dx + dy * dy).sqrt();
+    if len <= 0.0 {
+        return None;
+    }
+    let dir = (dx / len, dy / len);
+    let perp = (-dir.1, dir.0);
+    let head_len = (thickness * 4.0).max(10.0);
+    let p1 = (
+        tip.0 - dir.0 * head_len + perp.0 * head_len * 0.4,
+        tip.1 - dir.1 * head_len + perp.1 * head_len * 0.4,
+    );
+    let p2 = (
+        tip.0 - dir.0 * head_len - perp.0 * head_len * 0.4,
+        tip.1 - dir.1 * head_len - perp.1 * head_len * 0.4,
+    );
+    Some(match shape {
+        HeadShape::Triangle => ArrowHead::Triangle(vec![tip, p1, p2]),
+        HeadShape::Open => ArrowHead::Open(vec![p1, tip, p2]),
+        HeadShape::Dot => ArrowHead::Dot {
+            center: tip,
+            radius: (thickness * 1.5).max(4.0),
+        },
+    })
+}
+
+/// The points an arrow's shaft runs through: its two ends, or the quadratic
+/// Bézier through `control` flattened into `CURVE_SEGMENTS` segments.
+fn arrow_curve(start: (f32, f32), control: Option<(f32, f32)>, end: (f32, f32)) -> Vec<(f32, f32)> {
+    let Some(c) = control else {
+        return vec![start, end];
+    };
+    (0..=CURVE_SEGMENTS)
+        .map(|i| quadratic_point(start, c, end, i as f32 / CURVE_SEGMENTS as f32))
+        .collect()
+}
+
+fn quadratic_point(start: (f32, f32), control: (f32, f32), end: (f32, f32), t: f32) -> (f32, f32) {
+    let u = 1.0 - t;
+    (
+        u * u * start.0 + 2.0 * u * t * control.0 + t * t * end.0,
+        u * u * start.1 + 2.0 * u * t * control.1 + t * t * end.1,
+    )
+}
+
+/// The point halfway along an arrow, where its bend handle sits.
+fn curve_midpoint(start: (f32, f32), control: Option<(f32, f32)>, end: (f32, f32)) -> (f32, f32) {
+    let c = control.unwrap_or(((start.0 + end.0) * 0.5, (start.1 + end.1) * 0.5));
+    quadratic_point(start, c, end, 0.5)
+}
+
+/// The heads of an arrow from `start` to `end`, curved by `control`. Each
+/// head points along the curve's tangent at its end, which for a quadratic
+/// Bézier runs through the control point.
+fn arrow_heads(
+    start: (f32, f32),
+    control: Option<(f32, f32)>,
+    end: (f32, f32),
     thickness: f32,
-    color: [u8; 4],
-) {
-    let steps = ((rx.max(ry) * std::f32::consts::TAU) as usize).max(64);
-    let half_t = (thickness / 2.0).max(0.5) as i32;
-    let (w, h) = (img.width() as i32, img.height() as i32);
-    for i in 0..steps {
-        let angle = i as f32 / steps as f32 * std::f32::consts::TAU;
-        let px = (cx + rx * angle.cos()) as i32;
-        let py = (cy + ry * angle.sin()) as i32;
-        for oy in -half_t..=half_t {
-            for ox in -half_t..=half_t {
-                let x = px + ox;
-                let y = py + oy;
-                if x >= 0 && x < w && y >= 0 && y < h {
-                    img.put_pixel(x as u32, y as u32, image::Rgba(color));
+    heads: ArrowHeads,
+    shape: HeadShape,
+) -> Vec<ArrowHead> {
+    let toward = |tip: (f32, f32), other: (f32, f32)| match control {
+        Some(c) if c != tip => c,
+        _ => other,
+    };
+    let mut tips = Vec::new();
+    if matches!(heads, ArrowHeads::Start | ArrowHeads::Both) {
+        tips.push((toward(start, end), start));
+    }
+    if matches!(heads, ArrowHeads::End | ArrowHeads::Both) {
+        tips.push((toward(end, start), end));
+    }
+    tips.into_iter()
+        .filter_map(|(from, tip)| arrow_head(from, tip, thickness, shape))
+        .collect()
+}
+
+/// The layout of a step badge: a circle around the number's glyphs, which
+/// are centered by their ink rather than their line box so digits sit in
+/// the middle.
+struct StepBadge {
+    radius: f32,
+    /// Width of the ring around the circle, in the label's color.
+    outline: f32,
+    /// Top-left corner of the label's line box.
+    label_pos: (f32, f32),
+}
+
+fn step_badge(center: (f32, f32), number: u32, font_size: f32) -> StepBadge {
+    let ink = text_path((0.0, 0.0), &number.to_string(), font_size).map(|path| path.bounds());
+    let (ink_center, ink_size) = match ink {
+        Some(ink) => (
+            ((ink.left() + ink.right()) * 0.5, (ink.top() + ink.bottom()) * 0.5),
+            ink.width().max(ink.height()),
+        ),
+        None => ((0.0, 0.0), 0.0),
+    };
+    StepBadge {
+        radius: (ink_size * 0.5 + font_size * 0.35).max(font_size * 0.7),
+        outline: (font_size * 0.08).max(1.0),
+        label_pos: (center.0 - ink_center.0, center.1 - ink_center.1),
+    }
+}
+
+/// Where a dimension line's ticks and label go.
+struct DimensionLayout {
+    /// The segments across each end, perpendicular to the line.
+    ticks: [((f32, f32), (f32, f32)); 2],
+    label: String,
+    /// Top-left corner of the label's line box.
+    label_pos: (f32, f32),
+    /// The backdrop keeping the label legible on busy photos.
+    backdrop_min: (f32, f32),
+    backdrop_max: (f32, f32),
+}
+
+/// Lays out a dimension line's label beside its middle, on the upper side
+/// (the left one for a vertical line), clear of the line itself.
+fn dimension_layout(
+    start: (f32, f32),
+    end: (f32, f32),
+    thickness: f32,
+    font_size: f32,
+    calibration: Option<&Calibration>,
+) -> DimensionLayout {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    let mut normal = if len > 0.0 { (-dy / len, dx / len) } else { (0.0, -1.0) };
+    if normal.1 > 0.0 || (normal.1 == 0.0 && normal.0 > 0.0) {
+        normal = (-normal.0, -normal.1);
+    }
+    let half_tick = (thickness * 2.0).max(5.0);
+    let tick = |p: (f32, f32)| {
+        (
+            (p.0 + normal.0 * half_tick, p.1 + normal.1 * half_tick),
+            (p.0 - normal.0 * half_tick, p.1 - normal.1 * half_tick),
+        )
+    };
+
+    let label = dimension_label(len, calibration);
+    let (_, row_height) = text_metrics(font_size);
+    let padding = font_size * 0.15;
+    let half_size = (
+        text_width(&label, font_size) * 0.5 + padding,
+        row_height * 0.5 + padding,
+    );
+    // Push the label out along the normal until its box clears the line.
+    let offset = thickness * 0.5 + padding + normal.0.abs() * half_size.0 + normal.1.abs() * half_size.1;
+    let center = (
+        (start.0 + end.0) * 0.5 + normal.0 * offset,
+        (start.1 + end.1) * 0.5 + normal.1 * offset,
+    );
+    DimensionLayout {
+        ticks: [tick(start), tick(end)],
+        label,
+        label_pos: (
+            center.0 - half_size.0 + padding,
+            center.1 - half_size.1 + padding,
+        ),
+        backdrop_min: (center.0 - half_size.0, center.1 - half_size.1),
+        backdrop_max: (center.0 + half_size.0, center.1 + half_size.1),
+    }
+}
+
+/// The translucent backdrop behind a label drawn in `color`.
+fn dimension_backdrop(color: &Color4) -> Color4 {
+    Color4 {
+        a: 0.75,
+        ..contrasting_color(color)
+    }
+}
+
+/// A length in image pixels as a dimension label: in the calibrated unit
+/// when there is a usable calibration, otherwise in pixels.
+fn dimension_label(pixels: f32, calibration: Option<&Calibration>) -> String {
+    match calibration.filter(|c| c.pixels > 0.0) {
+        Some(c) => {
+            let value = c.to_units(pixels);
+            let decimals = match value.abs() {
+                v if v >= 100.0 => 0,
+                v if v >= 10.0 => 1,
+                _ => 2,
+            };
+            format!("{value:.decimals$} {}", c.unit).trim_end().to_string()
+        }
+        None => format!("{pixels:.0} px"),
+    }
+}
+
+/// The frame a magnifier shows its source in, as normalized min and max
+/// corners.
+fn magnifier_frame(
+    source_min: (f32, f32),
+    source_max: (f32, f32),
+    frame: (f32, f32),
+    zoom: f32,
+) -> ((f32, f32), (f32, f32)) {
+    let size = (
+        (source_max.0 - source_min.0).abs() * zoom,
+        (source_max.1 - source_min.1).abs() * zoom,
+    );
+    (frame, (frame.0 + size.0, frame.1 + size.1))
+}
+
+/// The line joining a magnifier's source to its frame, between the points
+/// where the line through their centers leaves each box. `None` when the
+/// boxes overlap.
+fn magnifier_connector(
+    source: ((f32, f32), (f32, f32)),
+    frame: ((f32, f32), (f32, f32)),
+) -> Option<((f32, f32), (f32, f32))> {
+    let rect = |(a, b): ((f32, f32), (f32, f32))| egui::Rect::from_two_pos(egui::pos2(a.0, a.1), egui::pos2(b.0, b.1));
+    let (source, frame) = (rect(source), rect(frame));
+    if source.intersects(frame) {
+        return None;
+    }
+    let exit = |r: egui::Rect, toward: egui::Pos2| {
+        let dir = toward - r.center();
+        let t = (r.width() * 0.5 / dir.x.abs()).min(r.height() * 0.5 / dir.y.abs());
+        let p = r.center() + dir * t;
+        (p.x, p.y)
+    };
+    Some((exit(source, frame.center()), exit(frame, source.center())))
+}
+
+/// The part of an inset whose source lies on an image of `image_size`, as
+/// `[src_min, src_max, min, max]` with both boxes normalized. `None` when
+/// none of the source does.
+fn clip_inset(
+    src_min: (f32, f32),
+    src_max: (f32, f32),
+    min: (f32, f32),
+    max: (f32, f32),
+    image_size: (f32, f32),
+) -> Option<[(f32, f32); 4]> {
+    let clip = |src: (f32, f32), dst: (f32, f32), limit: f32| {
+        let (lo, hi) = (src.0.min(src.1), src.0.max(src.1));
+        let (dlo, dhi) = (dst.0.min(dst.1), dst.0.max(dst.1));
+        if hi <= lo {
+            return None;
+        }
+        let scale = (dhi - dlo) / (hi - lo);
+        let (clo, chi) = (lo.max(0.0), hi.min(limit));
+        (clo < chi).then_some(((clo, chi), (dlo + (clo - lo) * scale, dlo + (chi - lo) * scale)))
+    };
+    let ((sx0, sx1), (dx0, dx1)) = clip((src_min.0, src_max.0), (min.0, max.0), image_size.0)?;
+    let ((sy0, sy1), (dy0, dy1)) = clip((src_min.1, src_max.1), (min.1, max.1), image_size.1)?;
+    Some([(sx0, sy0), (sx1, sy1), (dx0, dy0), (dx1, dy1)])
+}
+
+/// Space between a callout's edge and its text, and the radius of its
+/// corners, as fractions of the font size.
+const CALLOUT_PADDING: f32 = 0.5;
+const CALLOUT_CORNER_RADIUS: f32 = 0.5;
+
+/// Points per rounded corner of a callout, not counting its ends.
+const CALLOUT_CORNER_STEPS: usize = 6;
+
+/// Where a callout's box and text end up once the text is wrapped to the
+/// box's width.
+struct CalloutLayout {
+    min: (f32, f32),
+    max: (f32, f32),
+    text_pos: (f32, f32),
+    /// The content with line breaks added by `wrap_text`.
+    wrapped: String,
+}
+
+fn callout_layout(min: (f32, f32), max: (f32, f32), content: &str, font_size: f32) -> CalloutLayout {
+    let (min, max) = ((min.0.min(max.0), min.1.min(max.1)), (min.0.max(max.0), min.1.max(max.1)));
+    let padding = font_size * CALLOUT_PADDING;
+    let wrapped = wrap_text(content, font_size, max.0 - min.0 - 2.0 * padding);
+    let (_, row_height) = text_metrics(font_size);
+    let text_height = wrapped.split('\n').count() as f32 * row_height;
+    CalloutLayout {
+        min,
+        max: (max.0, max.1.max(min.1 + text_height + 2.0 * padding)),
+        text_pos: (min.0 + padding, min.1 + padding),
+        wrapped,
+    }
+}
+
+/// The outline of a callout's box, with rounded corners and, unless
+/// `anchor` is inside the box, a tail out to it from the side facing it.
+fn callout_outline(min: (f32, f32), max: (f32, f32), anchor: (f32, f32), font_size: f32) -> Vec<(f32, f32)> {
+    use std::f32::consts::FRAC_PI_2;
+    let r = (font_size * CALLOUT_CORNER_RADIUS)
+        .min((max.0 - min.0) * 0.5)
+        .min((max.1 - min.1) * 0.5)
+        .max(0.0);
+    // The straight part of each side, clockwise from the top, and the
+    // center of the corner that follows it.
+    let sides = [
+        ((min.0 + r, min.1), (max.0 - r, min.1), (max.0 - r, min.1 + r)),
+        ((max.0, min.1 + r), (max.0, max.1 - r), (max.0 - r, max.1 - r)),
+        ((max.0 - r, max.1), (min.0 + r, max.1), (min.0 + r, max.1 - r)),
+        ((min.0, max.1 - r), (min.0, min.1 + r), (min.0 + r, min.1 + r)),
+    ];
+    let beyond = |v: f32, lo: f32, hi: f32| (lo - v).max(v - hi).max(0.0);
+    let (out_x, out_y) = (beyond(anchor.0, min.0, max.0), beyond(anchor.1, min.1, max.1));
+    let tail_side = if out_x == 0.0 && out_y == 0.0 {
+        None
+    } else if out_y >= out_x {
+        Some(if anchor.1 < min.1 { 0 } else { 2 })
+    } else {
+        Some(if anchor.0 > max.0 { 1 } else { 3 })
+    };
+
+    let mut points = Vec::new();
+    for (i, &(start, end, corner)) in sides.iter().enumerate() {
+        points.push(start);
+        if tail_side == Some(i) {
+            let along = (end.0 - start.0, end.1 - start.1);
+            let len = (along.0 * along.0 + along.1 * along.1).sqrt();
+            if len > 0.0 {
+                let dir = (along.0 / len, along.1 / len);
+                let half = (font_size * 0.5).min(len * 0.5);
+                let t = ((anchor.0 - start.0) * dir.0 + (anchor.1 - start.1) * dir.1).clamp(half, len - half);
+                points.push((start.0 + dir.0 * (t - half), start.1 + dir.1 * (t - half)));
+                points.push(anchor);
+                points.push((start.0 + dir.0 * (t + half), start.1 + dir.1 * (t + half)));
+            } else {
+                points.push(anchor);
+            }
+        }
+        points.push(end);
+        let from = i as f32 * FRAC_PI_2 - FRAC_PI_2;
+        for step in 1..=CALLOUT_CORNER_STEPS {
+            let a = from + FRAC_PI_2 * step as f32 / (CALLOUT_CORNER_STEPS + 1) as f32;
+            points.push((corner.0 + r * a.cos(), corner.1 + r * a.sin()));
+        }
+    }
+    points.dedup_by(|a, b| (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3);
+    points
+}
+
+/// Black or white, whichever reads better on `background`.
+fn contrasting_color(background: &Color4) -> Color4 {
+    let luma = 0.2126 * background.r + 0.7152 * background.g + 0.0722 * background.b;
+    let v = if luma > 0.6 { 0.0 } else { 1.0 };
+    Color4 {
+        r: v,
+        g: v,
+        b: v,
+        a: background.a,
+    }
+}
+
+fn fill_color(fill: &Fill) -> Option<Color4> {
+    match fill {
+        Fill::Color(c) => Some(c.clone()),
+        _ => None,
+    }
+}
+
+/// The prim that obscures the image under a blur, pixelate or redact fill,
+/// or lights it for a spotlight fill.
+fn obscuring_prim(fill: &Fill, min: (f32, f32), max: (f32, f32), mask: FillMask) -> Option<ScenePrim> {
+    let effect = match fill {
+        Fill::None | Fill::Color(_) => return None,
+        Fill::Spotlight { dim, grayscale } => {
+            return Some(ScenePrim::Spotlight {
+                min,
+                max,
+                dim: *dim,
+                grayscale: *grayscale,
+                mask,
+            })
+        }
+        Fill::Blur(sigma) => {
+            return Some(ScenePrim::Blur {
+                min,
+                max,
+                sigma: *sigma,
+                mask,
+            })
+        }
+        Fill::Pixelate(block) => Redaction::Pixelate(*block),
+        Fill::Redact => Redaction::Solid,
+    };
+    Some(ScenePrim::Redaction {
+        min,
+        max,
+        effect,
+        mask,
+    })
+}
+
+/// The primitives an annotation draws as. `calibration` is the image's, for
+/// Dimension labels.
+fn annotation_prims(kind: &AnnotationKind, calibration: Option<&Calibration>) -> Vec<ScenePrim> {
+    match kind {
+        AnnotationKind::Arrow {
+            start,
+            end,
+            color,
+            thickness,
+            style,
+            heads,
+            head_shape,
+            control,
+        } => {
+            let mut prims = vec![ScenePrim::Polyline {
+                points: arrow_curve(*start, *control, *end),
+                closed: false,
+                fill: None,
+                stroke: Some(PrimStroke {
+                    width: *thickness,
+                    color: color.clone(),
+                    style: *style,
+                }),
+            }];
+            prims.extend(
+                arrow_heads(*start, *control, *end, *thickness, *heads, *head_shape)
+                    .into_iter()
+                    .map(|head| head.prim(color, *thickness)),
+            );
+            prims
+        }
+        AnnotationKind::Rectangle {
+            min,
+            max,
+            color,
+            thickness,
+            fill,
+            style,
+        } => {
+            let mut prims: Vec<ScenePrim> =
+                obscuring_prim(fill, *min, *max, FillMask::Rect).into_iter().collect();
+            prims.push(ScenePrim::Rect {
+                min: *min,
+                max: *max,
+                fill: fill_color(fill),
+                stroke: Some(PrimStroke {
+                    width: *thickness,
+                    color: color.clone(),
+                    style: *style,
+                }),
+            });
+            prims
+        }
+        AnnotationKind::Oval {
+            min,
+            max,
+            color,
+            thickness,
+            fill,
+            style,
+        } => {
+            let mut prims: Vec<ScenePrim> =
+                obscuring_prim(fill, *min, *max, FillMask::Oval).into_iter().collect();
+            prims.push(ScenePrim::Ellipse {
+                center: ((min.0 + max.0) * 0.5, (min.1 + max.1) * 0.5),
+                radii: ((max.0 - min.0).abs() * 0.5, (max.1 - min.1).abs() * 0.5),
+                fill: fill_color(fill),
+                stroke: Some(PrimStroke {
+                    width: *thickness,
+                    color: color.clone(),
+                    style: *style,
+                }),
+            });
+            prims
+        }
+        AnnotationKind::Text {
+            pos,
+            content,
+            font_size,
+            color,
+        } => vec![ScenePrim::Text {
+            pos: *pos,
+            content: content.clone(),
+            font_size: *font_size,
+            color: color.clone(),
+        }],
+        AnnotationKind::Path {
+            points,
+            color,
+            thickness,
+            highlight,
+        } => vec![ScenePrim::Path {
+            points: points.clone(),
+            stroke: PrimStroke {
+                width: *thickness,
+                color: color.clone(),
+                style: StrokeStyle::Solid,
+            },
+            multiply: *highlight,
+        }],
+        AnnotationKind::Polygon {
+            points,
+            color,
+            thickness,
+            fill,
+        } => {
+            let bounds = egui::Rect::from_points(
+                &points.iter().map(|p| egui::pos2(p.0, p.1)).collect::<Vec<_>>(),
+            );
+            let mut prims: Vec<ScenePrim> = obscuring_prim(
+                fill,
+                (bounds.min.x, bounds.min.y),
+                (bounds.max.x, bounds.max.y),
+                FillMask::Polygon(points.clone()),
+            )
+            .into_iter()
+            .collect();
+            prims.push(ScenePrim::Polyline {
+                points: points.clone(),
+                closed: true,
+                fill: fill_color(fill),
+                stroke: Some(PrimStroke {
+                    width: *thickness,
+                    color: color.clone(),
+                    style: StrokeStyle::Solid,
+                }),
+            });
+            prims
+        }
+        AnnotationKind::Polyline {
+            points,
+            color,
+            thickness,
+        } => vec![ScenePrim::Polyline {
+            points: points.clone(),
+            closed: false,
+            fill: None,
+            stroke: Some(PrimStroke {
+                width: *thickness,
+                color: color.clone(),
+                style: StrokeStyle::Solid,
+            }),
+        }],
+        AnnotationKind::Callout {
+            min,
+            max,
+            anchor,
+            content,
+            font_size,
+            background,
+        } => {
+            let layout = callout_layout(*min, *max, content, *font_size);
+            vec![
+                ScenePrim::Polyline {
+                    points: callout_outline(layout.min, layout.max, *anchor, *font_size),
+                    closed: true,
+                    fill: Some(background.clone()),
+                    stroke: None,
+                },
+                ScenePrim::Text {
+                    pos: layout.text_pos,
+                    content: layout.wrapped,
+                    font_size: *font_size,
+                    color: contrasting_color(background),
+                },
+            ]
+        }
+        AnnotationKind::Step {
+            pos,
+            number,
+            color,
+            font_size,
+        } => {
+            let badge = step_badge(*pos, *number, *font_size);
+            vec![
+                ScenePrim::Ellipse {
+                    center: *pos,
+                    radii: (badge.radius, badge.radius),
+                    fill: Some(color.clone()),
+                    stroke: Some(PrimStroke {
+                        width: badge.outline,
+                        color: contrasting_color(color),
+                        style: StrokeStyle::Solid,
+                    }),
+                },
+                ScenePrim::Text {
+                    pos: badge.label_pos,
+                    content: number.to_string(),
+                    font_size: *font_size,
+                    color: contrasting_color(color),
+                },
+            ]
+        }
+        AnnotationKind::Magnifier {
+            source_min,
+            source_max,
+            frame,
+            zoom,
+            color,
+            thickness,
+            connector,
+        } => {
+            let source = (
+                (source_min.0.min(source_max.0), source_min.1.min(source_max.1)),
+                (source_min.0.max(source_max.0), source_min.1.max(source_max.1)),
+            );
+            let (min, max) = magnifier_frame(*source_min, *source_max, *frame, *zoom);
+            let outline = |(min, max): ((f32, f32), (f32, f32))| ScenePrim::Rect {
+                min,
+                max,
+                fill: None,
+                stroke: Some(PrimStroke {
+                    width: *thickness,
+                    color: color.clone(),
+                    style: StrokeStyle::Solid,
+                }),
+            };
+            let mut prims = vec![
+                outline(source),
+                ScenePrim::Inset {
+                    src_min: source.0,
+                    src_max: source.1,
+                    min,
+                    max,
+                },
+                outline((min, max)),
+            ];
+            let link = if *connector { magnifier_connector(source, (min, max)) } else { None };
+            if let Some((from, to)) = link {
+                prims.insert(
+                    1,
+                    ScenePrim::Line {
+                        from,
+                        to,
+                        color: color.clone(),
+                        width: *thickness,
+                    },
+                );
+            }
+            prims
+        }
+        AnnotationKind::Dimension {
+            start,
+            end,
+            color,
+            thickness,
+            font_size,
+        } => {
+            let layout = dimension_layout(*start, *end, *thickness, *font_size, calibration);
+            let line = |from, to| ScenePrim::Line {
+                from,
+                to,
+                color: color.clone(),
+                width: *thickness,
+            };
+            let [(t0, t1), (t2, t3)] = layout.ticks;
+            vec![
+                line(*start, *end),
+                line(t0, t1),
+                line(t2, t3),
+                ScenePrim::Rect {
+                    min: layout.backdrop_min,
+                    max: layout.backdrop_max,
+                    fill: Some(dimension_backdrop(color)),
+                    stroke: None,
+                },
+                ScenePrim::Text {
+                    pos: layout.label_pos,
+                    content: layout.label,
+                    font_size: *font_size,
+                    color: color.clone(),
+                },
+            ]
+        }
+    }
+}
+
+/// Image-space bounds of scene primitives, including stroke widths.
+fn prims_bounds(prims: &[ScenePrim]) -> egui::Rect {
+    let pos = |p: &(f32, f32)| egui::pos2(p.0, p.1);
+    let half_width = |stroke: &Option<PrimStroke>| stroke.as_ref().map_or(0.0, |s| s.width * 0.5);
+    let mut bounds = egui::Rect::NOTHING;
+    for prim in prims {
+        let rect = match prim {
+            ScenePrim::Line { from, to, width, .. } => {
+                egui::Rect::from_two_pos(pos(from), pos(to)).expand(width * 0.5)
+            }
+            ScenePrim::Path { points, stroke, .. } => {
+                egui::Rect::from_points(&points.iter().map(pos).collect::<Vec<_>>())
+                    .expand(stroke.width * 0.5)
+            }
+            ScenePrim::Polyline { points, stroke, .. } => {
+                egui::Rect::from_points(&points.iter().map(pos).collect::<Vec<_>>())
+                    .expand(half_width(stroke))
+            }
+            ScenePrim::Polygon { points, .. } => {
+                egui::Rect::from_points(&points.iter().map(pos).collect::<Vec<_>>())
+            }
+            ScenePrim::Rect { min, max, stroke, .. } => {
+                egui::Rect::from_two_pos(pos(min), pos(max)).expand(half_width(stroke))
+            }
+            ScenePrim::Ellipse {
+                center,
+                radii,
+                stroke,
+                ..
+            } => egui::Rect::from_center_size(pos(center), egui::vec2(radii.0, radii.1) * 2.0)
+                .expand(half_width(stroke)),
+            ScenePrim::Blur { min, max, .. }
+            | ScenePrim::Redaction { min, max, .. }
+            | ScenePrim::Spotlight { min, max, .. }
+            | ScenePrim::Inset { min, max, .. } => egui::Rect::from_two_pos(pos(min), pos(max)),
+            ScenePrim::Text {
+                pos: at,
+                content,
+                font_size,
+                ..
+            } => {
+                let ink = text_path(*at, content, *font_size).map(|path| {
+                    let b = path.bounds();
+                    egui::Rect::from_min_max(
+                        egui::pos2(b.left(), b.top()),
+                        egui::pos2(b.right(), b.bottom()),
+                    )
+                });
+                egui::Rect::from_min_max(pos(at), pos(at)).union(ink.unwrap_or(egui::Rect::NOTHING))
+            }
+        };
+        bounds = bounds.union(rect);
+    }
+    bounds
+}
+
+/// The image every export draws annotations on, and insets sample:
+/// redacted by `redact_source`, then dimmed by the spotlights.
+fn export_source(raw: &DynamicImage, annotations: &[Annotation]) -> Result<RgbaImage, String> {
+    let mut img = redact_source(raw, annotations)?.to_rgba8();
+    if let Some(spot) = spotlight(annotations) {
+        apply_spotlight(&mut img, &spot);
+    }
+    Ok(img)
+}
+
+/// Flattens the annotations onto a redacted copy of the source image.
+fn render_annotated(
+    raw: &DynamicImage,
+    annotations: &[Annotation],
+    calibration: Option<&Calibration>,
+) -> Result<RgbaImage, String> {
+    let img = export_source(raw, annotations)?;
+    let Some(mut pixmap) = rgba_to_pixmap(&img) else {
+        return Ok(img);
+    };
+    let source = pixmap.clone();
+    for ann in annotations {
+        rasterize_prims(&mut pixmap, &annotation_prims(&ann.kind, calibration), &source);
+    }
+    Ok(pixmap_to_rgba(&pixmap))
+}
+
+/// Draws scene primitives with anti-aliased coverage and source-over
+/// blending. Strokes use butt caps and miter joins, like egui's. Insets
+/// magnify `source`, the image before any annotation was drawn.
+fn rasterize_prims(pixmap: &mut tiny_skia::Pixmap, prims: &[ScenePrim], source: &tiny_skia::Pixmap) {
+    let identity = tiny_skia::Transform::identity();
+    for prim in prims {
+        match prim {
+            ScenePrim::Line {
+                from,
+                to,
+                color,
+                width,
+            } => {
+                let mut pb = tiny_skia::PathBuilder::new();
+                pb.move_to(from.0, from.1);
+                pb.line_to(to.0, to.1);
+                if let Some(path) = pb.finish() {
+                    let stroke = PrimStroke {
+                        width: *width,
+                        color: color.clone(),
+                        style: StrokeStyle::Solid,
+                    };
+                    stroke_on_pixmap(pixmap, &path, &stroke);
+                }
+            }
+            ScenePrim::Path {
+                points,
+                stroke,
+                multiply,
+            } => {
+                let Some(path) = polyline_path(points) else {
+                    continue;
+                };
+                let mut paint = skia_paint(&stroke.color);
+                if *multiply {
+                    paint.blend_mode = tiny_skia::BlendMode::Multiply;
+                }
+                let skia_stroke = tiny_skia::Stroke {
+                    width: stroke.width,
+                    line_cap: tiny_skia::LineCap::Round,
+                    line_join: tiny_skia::LineJoin::Round,
+                    ..Default::default()
+                };
+                pixmap.stroke_path(&path, &paint, &skia_stroke, identity, None);
+            }
+            ScenePrim::Polyline {
+                points,
+                closed,
+                fill,
+                stroke,
+            } => {
+                let path = if *closed {
+                    polygon_path(points)
+                } else {
+                    polyline_path(points)
+                };
+                if let Some(path) = path {
+                    fill_and_stroke_on_pixmap(pixmap, &path, fill, stroke);
+                }
+            }
+            ScenePrim::Polygon { points, color } => {
+                if let Some(path) = polygon_path(points) {
+                    pixmap.fill_path(
+                        &path,
+                        &skia_paint(color),
+                        tiny_skia::FillRule::Winding,
+                        identity,
+                        None,
+                    );
+                }
+            }
+            ScenePrim::Rect {
+                min,
+                max,
+                fill,
+                stroke,
+            } => {
+                let rect = tiny_skia::Rect::from_ltrb(
+                    min.0.min(max.0),
+                    min.1.min(max.1),
+                    min.0.max(max.0),
+                    min.1.max(max.1),
+                );
+                if let Some(path) = rect.map(tiny_skia::PathBuilder::from_rect) {
+                    fill_and_stroke_on_pixmap(pixmap, &path, fill, stroke);
+                }
+            }
+            ScenePrim::Ellipse {
+                center,
+                radii,
+                fill,
+                stroke,
+            } => {
+                if let Some(path) = ellipse_path(*center, *radii) {
+                    fill_and_stroke_on_pixmap(pixmap, &path, fill, stroke);
+                }
+            }
+            ScenePrim::Blur {
+                min,
+                max,
+                sigma,
+                mask,
+            } => {
+                let base = pixmap_to_rgba(source);
+                let Some((ox, oy, patch)) = blurred_patch(&base, min.0, min.1, max.0, max.1, *sigma, mask)
+                else {
+                    continue;
+                };
+                let Some(patch) = rgba_to_pixmap(&patch) else {
+                    continue;
+                };
+                pixmap.draw_pixmap(
+                    ox as i32,
+                    oy as i32,
+                    patch.as_ref(),
+                    &tiny_skia::PixmapPaint::default(),
+                    identity,
+                    None,
+                );
+            }
+            // Already applied to the source image by `redact_source` and
+            // `apply_spotlight`.
+            ScenePrim::Redaction { .. } | ScenePrim::Spotlight { .. } => {}
+            ScenePrim::Inset {
+                src_min,
+                src_max,
+                min,
+                max,
+            } => {
+                let size = (source.width() as f32, source.height() as f32);
+                let Some([s0, s1, d0, d1]) = clip_inset(*src_min, *src_max, *min, *max, size) else {
+                    continue;
+                };
+                let Some(rect) = tiny_skia::Rect::from_ltrb(d0.0, d0.1, d1.0, d1.1) else {
+                    continue;
+                };
+                let (sx, sy) = ((d1.0 - d0.0) / (s1.0 - s0.0), (d1.1 - d0.1) / (s1.1 - s0.1));
+                let paint = tiny_skia::Paint {
+                    shader: tiny_skia::Pattern::new(
+                        source.as_ref(),
+                        tiny_skia::SpreadMode::Pad,
+                        tiny_skia::FilterQuality::Bilinear,
+                        1.0,
+                        tiny_skia::Transform::from_row(sx, 0.0, 0.0, sy, d0.0 - s0.0 * sx, d0.1 - s0.1 * sy),
+                    ),
+                    ..Default::default()
+                };
+                pixmap.fill_rect(rect, &paint, identity, None);
+            }
+            ScenePrim::Text {
+                pos,
+                content,
+                font_size,
+                color,
+            } => {
+                if let Some(path) = text_path(*pos, content, *font_size) {
+                    pixmap.fill_path(
+                        &path,
+                        &skia_paint(color),
+                        tiny_skia::FillRule::Winding,
+                        identity,
+                        None,
+                    );
                 }
             }
         }
     }
 }
 
-fn fill_oval_on_image(
-    img: &mut RgbaImage,
-    cx: f32,
-    cy: f32,
-    rx: f32,
-    ry: f32,
-    color: [u8; 4],
+/// What multiply-blending `color` over an opaque pixel leaves of each of
+/// its channels, as an opaque color to tint that pixel with.
+fn multiply_tint(color: &Color4) -> egui::Color32 {
+    let [r, g, b, a] = color.to_rgba8().map(|c| c as f32 / 255.0);
+    let keep = |c: f32| ((1.0 - a + a * c) * 255.0).round() as u8;
+    egui::Color32::from_rgb(keep(r), keep(g), keep(b))
+}
+
+fn skia_paint(color: &Color4) -> tiny_skia::Paint<'static> {
+    let [r, g, b, a] = color.to_rgba8();
+    let mut paint = tiny_skia::Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    paint
+}
+
+fn stroke_on_pixmap(pixmap: &mut tiny_skia::Pixmap, path: &tiny_skia::Path, stroke: &PrimStroke) {
+    let skia_stroke = tiny_skia::Stroke {
+        width: stroke.width,
+        line_cap: if stroke.style == StrokeStyle::Dotted {
+            tiny_skia::LineCap::Round
+        } else {
+            tiny_skia::LineCap::Butt
+        },
+        line_join: tiny_skia::LineJoin::Miter,
+        dash: stroke
+            .style
+            .dash_pattern(stroke.width)
+            .and_then(|pattern| tiny_skia::StrokeDash::new(pattern.to_vec(), 0.0)),
+        ..Default::default()
+    };
+    pixmap.stroke_path(
+        path,
+        &skia_paint(&stroke.color),
+        &skia_stroke,
+        tiny_skia::Transform::identity(),
+        None,
+    );
+}
+
+fn fill_and_stroke_on_pixmap(
+    pixmap: &mut tiny_skia::Pixmap,
+    path: &tiny_skia::Path,
+    fill: &Option<Color4>,
+    stroke: &Option<PrimStroke>,
 ) {
-    let (w, h) = (img.width() as i32, img.height() as i32);
-    let x0 = ((cx - rx) as i32).max(0);
-    let x1 = ((cx + rx) as i32 + 1).min(w);
-    let y0 = ((cy - ry) as i32).max(0);
-    let y1 = ((cy + ry) as i32 + 1).min(h);
-    for y in y0..y1 {
-        for x in x0..x1 {
-            let dx = (x as f32 - cx) / rx;
-            let dy = (y as f32 - cy) / ry;
-            if dx * dx + dy * dy <= 1.0 {
-                img.put_pixel(x as u32, y as u32, image::Rgba(color));
+    if let Some(fc) = fill {
+        pixmap.fill_path(
+            path,
+            &skia_paint(fc),
+            tiny_skia::FillRule::Winding,
+            tiny_skia::Transform::identity(),
+            None,
+        );
+    }
+    if let Some(s) = stroke {
+        stroke_on_pixmap(pixmap, path, s);
+    }
+}
+
+fn polyline_path(points: &[(f32, f32)]) -> Option<tiny_skia::Path> {
+    let (first, rest) = points.split_first()?;
+    let mut pb = tiny_skia::PathBuilder::new();
+    pb.move_to(first.0, first.1);
+    for p in rest {
+        pb.line_to(p.0, p.1);
+    }
+    pb.finish()
+}
+
+fn polygon_path(points: &[(f32, f32)]) -> Option<tiny_skia::Path> {
+    let (first, rest) = points.split_first()?;
+    let mut pb = tiny_skia::PathBuilder::new();
+    pb.move_to(first.0, first.1);
+    for p in rest {
+        pb.line_to(p.0, p.1);
+    }
+    pb.close();
+    pb.finish()
+}
+
+/// The outline of the region a fill with these bounds covers.
+fn fill_mask_path(min: (f32, f32), max: (f32, f32), mask: &FillMask) -> Option<tiny_skia::Path> {
+    match mask {
+        FillMask::Rect => tiny_skia::Rect::from_ltrb(
+            min.0.min(max.0),
+            min.1.min(max.1),
+            min.0.max(max.0),
+            min.1.max(max.1),
+        )
+        .map(tiny_skia::PathBuilder::from_rect),
+        FillMask::Oval => ellipse_path(
+            ((min.0 + max.0) * 0.5, (min.1 + max.1) * 0.5),
+            ((max.0 - min.0).abs() * 0.5, (max.1 - min.1).abs() * 0.5),
+        ),
+        FillMask::Polygon(points) => polygon_path(points),
+    }
+}
+
+/// `fill_mask_path` as a polygon, with ovals flattened into
+/// `ELLIPSE_SEGMENTS` sides, for the canvas's meshes.
+fn fill_mask_outline(min: (f32, f32), max: (f32, f32), mask: &FillMask) -> Vec<(f32, f32)> {
+    match mask {
+        FillMask::Rect => vec![min, (max.0, min.1), max, (min.0, max.1)],
+        FillMask::Oval => {
+            let center = ((min.0 + max.0) * 0.5, (min.1 + max.1) * 0.5);
+            let radii = ((max.0 - min.0).abs() * 0.5, (max.1 - min.1).abs() * 0.5);
+            (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    (center.0 + radii.0 * angle.cos(), center.1 + radii.1 * angle.sin())
+                })
+                .collect()
+        }
+        FillMask::Polygon(points) => points.clone(),
+    }
+}
+
+fn ellipse_path(center: (f32, f32), radii: (f32, f32)) -> Option<tiny_skia::Path> {
+    let rect = tiny_skia::Rect::from_xywh(
+        center.0 - radii.0,
+        center.1 - radii.1,
+        radii.0 * 2.0,
+        radii.1 * 2.0,
+    )?;
+    tiny_skia::PathBuilder::from_oval(rect)
+}
+
+/// Converts straight-alpha pixels to tiny-skia's premultiplied storage.
+/// Returns `None` for empty images, which tiny-skia can't represent.
+fn rgba_to_pixmap(img: &RgbaImage) -> Option<tiny_skia::Pixmap> {
+    let mut pixmap = tiny_skia::Pixmap::new(img.width(), img.height())?;
+    for (dst, src) in pixmap.pixels_mut().iter_mut().zip(img.pixels()) {
+        *dst = tiny_skia::ColorU8::from_rgba(src[0], src[1], src[2], src[3]).premultiply();
+    }
+    Some(pixmap)
+}
+
+fn pixmap_to_rgba(pixmap: &tiny_skia::Pixmap) -> RgbaImage {
+    let mut img = RgbaImage::new(pixmap.width(), pixmap.height());
+    for (dst, src) in img.pixels_mut().zip(pixmap.pixels()) {
+        let c = src.demultiply();
+        *dst = image::Rgba([c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+    img
+}
+
+fn point_to_segment_dist(p: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let ab = b - a;
+    let ap = p - a;
+    if ab.length_sq() == 0.0 {
+        return ap.length();
+    }
+    let t = ap.dot(ab) / ab.dot(ab);
+    let t = t.clamp(0.0, 1.0);
+    let closest = a + ab * t;
+    (p - closest).length()
+}
+
+/// Whether `p` lies inside the polygon, by the non-zero winding rule the
+/// exporters fill with.
+fn point_in_polygon(p: egui::Pos2, points: &[egui::Pos2]) -> bool {
+    let mut winding = 0;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let side = (b.x - a.x) * (p.y - a.y) - (p.x - a.x) * (b.y - a.y);
+        if a.y <= p.y {
+            if b.y > p.y && side > 0.0 {
+                winding += 1;
             }
+        } else if b.y <= p.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+/// Ear-clipping triangulation of a simple polygon, as index triples into
+/// `points`. egui only fills convex paths, so the canvas fills polygons
+/// through a mesh built from these.
+fn triangulate(points: &[egui::Pos2]) -> Vec<[u32; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    // Orient the turn test so that it's positive for convex corners
+    // whichever way the polygon winds.
+    let area: f32 = (0..n)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum();
+    let sign = area.signum();
+    let turn = |a: egui::Pos2, b: egui::Pos2, c: egui::Pos2| {
+        let (ab, ac) = (b - a, c - a);
+        (ab.x * ac.y - ab.y * ac.x) * sign
+    };
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let corner = |i: usize| (remaining[(i + m - 1) % m], remaining[i], remaining[(i + 1) % m]);
+        let ear = (0..m).find(|&i| {
+            let (ia, ib, ic) = corner(i);
+            let (a, b, c) = (points[ia], points[ib], points[ic]);
+            turn(a, b, c) > 0.0
+                && !remaining.iter().any(|&j| {
+                    let p = points[j];
+                    j != ia
+                        && j != ib
+                        && j != ic
+                        && turn(a, b, p) >= 0.0
+                        && turn(b, c, p) >= 0.0
+                        && turn(c, a, p) >= 0.0
+                })
+        });
+        // Self-intersecting outlines can run out of ears; fan out whatever
+        // is left rather than dropping it.
+        let Some(i) = ear else {
+            break;
+        };
+        let (ia, ib, ic) = corner(i);
+        triangles.push([ia as u32, ib as u32, ic as u32]);
+        remaining.remove(i);
+    }
+    for k in 1..remaining.len() - 1 {
+        triangles.push([
+            remaining[0] as u32,
+            remaining[k] as u32,
+            remaining[k + 1] as u32,
+        ]);
+    }
+    triangles
+}
+
+/// Ramer–Douglas–Peucker: drops the points that lie within `tolerance` of
+/// the segment between the points kept around them. The endpoints are
+/// always kept.
+fn simplify_path(points: &[egui::Pos2], tolerance: f32) -> Vec<egui::Pos2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((a, b)) = spans.pop() {
+        let farthest = (a + 1..b)
+            .map(|i| (i, point_to_segment_dist(points[i], points[a], points[b])))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, _)) = farthest.filter(|&(_, dist)| dist > tolerance) {
+            keep[i] = true;
+            spans.push((a, i));
+            spans.push((i, b));
         }
     }
+    points
+        .iter()
+        .zip(&keep)
+        .filter(|(_, &kept)| kept)
+        .map(|(p, _)| *p)
+        .collect()
 }
 
 /// Crops `base` to the given image-space bounds (clamped to the image),
-/// applies a gaussian blur with the given sigma, and, if `oval` is set,
-/// zeroes the alpha of pixels outside the inscribed ellipse so the caller
+/// applies a gaussian blur with the given sigma, and scales the alpha of
+/// each pixel by how much of it `mask` covers, anti-aliased, so the caller
 /// can composite the patch back with alpha blending. Returns the patch
 /// together with its top-left origin in image space.
 fn blurred_patch<V>(
@@ -859,7 +4040,7 @@ fn blurred_patch<V>(
     x1: f32,
     y1: f32,
     sigma: f32,
-    oval: bool,
+    mask: &FillMask,
 ) -> Option<(u32, u32, RgbaImage)>
 where
     V: image::GenericImageView<Pixel = image::Rgba<u8>> + 'static,
@@ -875,51 +4056,1729 @@ where
     let (lx, ty, rx, by) = (lx as u32, ty as u32, rx as u32, by as u32);
     let cropped = imageops::crop_imm(base, lx, ty, rx - lx, by - ty).to_image();
     let mut blurred = imageops::blur(&cropped, sigma.max(0.01));
-    if oval {
-        let cx = (rx - lx) as f32 * 0.5;
-        let cy = (by - ty) as f32 * 0.5;
-        let rrx = cx.max(1.0);
-        let rry = cy.max(1.0);
-        for (px, py, pixel) in blurred.enumerate_pixels_mut() {
-            let dx = (px as f32 + 0.5 - cx) / rrx;
-            let dy = (py as f32 + 0.5 - cy) / rry;
-            if dx * dx + dy * dy > 1.0 {
-                pixel[3] = 0;
-            }
+    if !matches!(mask, FillMask::Rect) {
+        let path = fill_mask_path((x0, y0), (x1, y1), mask)?;
+        let mut coverage = tiny_skia::Mask::new(rx - lx, by - ty)?;
+        let offset = tiny_skia::Transform::from_translate(-(lx as f32), -(ty as f32));
+        coverage.fill_path(&path, tiny_skia::FillRule::Winding, true, offset);
+        for (pixel, covered) in blurred.pixels_mut().zip(coverage.data()) {
+            pixel[3] = ((u16::from(pixel[3]) * u16::from(*covered) + 127) / 255) as u8;
         }
     }
     Some((lx, ty, blurred))
 }
 
-fn fill_rect_on_image(
-    img: &mut RgbaImage,
-    x0: f32,
-    y0: f32,
-    x1: f32,
-    y1: f32,
-    color: [u8; 4],
-) {
-    let (w, h) = (img.width() as i32, img.height() as i32);
-    let lx = (x0.min(x1) as i32).max(0);
-    let rx = (x0.max(x1) as i32 + 1).min(w);
-    let ty = (y0.min(y1) as i32).max(0);
-    let by = (y0.max(y1) as i32 + 1).min(h);
-    for y in ty..by {
-        for x in lx..rx {
-            img.put_pixel(x as u32, y as u32, image::Rgba(color));
+/// The font egui uses for `FontFamily::Proportional` out of the box, so that
+/// exported text has the same glyphs and metrics as the canvas.
+fn export_font() -> &'static ab_glyph::FontRef<'static> {
+    static FONT: std::sync::OnceLock<ab_glyph::FontRef<'static>> = std::sync::OnceLock::new();
+    FONT.get_or_init(|| {
+        ab_glyph::FontRef::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT)
+            .expect("bundled font should parse")
+    })
+}
+
+/// Scales the font the way epaint does: `font_size` is the em size, which
+/// ab_glyph expresses as a fraction of the ascent-to-descent height.
+fn export_font_scale(font_size: f32) -> ab_glyph::PxScale {
+    use ab_glyph::Font;
+    let font = export_font();
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    ab_glyph::PxScale::from(font_size * font.height_unscaled() / units_per_em)
+}
+
+/// The ascent and row height, in pixels, of the export font at
+/// `font_size`.
+fn text_metrics(font_size: f32) -> (f32, f32) {
+    use ab_glyph::{Font, ScaleFont};
+    let scaled = export_font().as_scaled(export_font_scale(font_size));
+    (
+        scaled.ascent(),
+        scaled.ascent() - scaled.descent() + scaled.line_gap(),
+    )
+}
+
+/// The advance width of one line of text in the export font, measured the
+/// way `text_path` lays it out.
+fn text_width(line: &str, font_size: f32) -> f32 {
+    use ab_glyph::{Font, ScaleFont};
+    let scaled = export_font().as_scaled(export_font_scale(font_size));
+    let mut width = 0.0;
+    let mut last = None;
+    for ch in line.chars() {
+        let id = scaled.glyph_id(ch);
+        if let Some(prev) = last {
+            width += scaled.kern(prev, id);
         }
+        width += scaled.h_advance(id);
+        last = Some(id);
     }
+    width
 }
 
-// ── eframe App impl ────────────────────────────────────────────────────────
+/// Breaks `content` into lines no wider than `max_width`, at spaces where
+/// possible and inside words too long for a line of their own. Existing
+/// line breaks are kept. The canvas and every export draw the result, so
+/// lines break in the same places everywhere.
+fn wrap_text(content: &str, font_size: f32, max_width: f32) -> String {
+    let fits = |s: &str| text_width(s, font_size) <= max_width;
+    let mut lines = Vec::new();
+    for paragraph in content.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let joined = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if line.is_empty() || fits(&joined) {
+                line = joined;
+            } else {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            }
+            while !fits(&line) && line.chars().count() > 1 {
+                // The longest prefix that fits, but at least one character.
+                let split = line
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .skip(2)
+                    .take_while(|&i| fits(&line[..i]))
+                    .last()
+                    .unwrap_or_else(|| line.char_indices().nth(1).map_or(line.len(), |(i, _)| i));
+                let rest = line.split_off(split);
+                lines.push(std::mem::replace(&mut line, rest));
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
 
-impl eframe::App for AnnotateApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.ensure_texture(ctx);
+/// Builds the glyph outlines of `content` with its top-left corner at
+/// `pos`, matching the placement of the galley `painter.galley` draws on
+/// the canvas: each row starts at the top and its baseline sits one ascent
+/// below.
+fn text_path(pos: (f32, f32), content: &str, font_size: f32) -> Option<tiny_skia::Path> {
+    use ab_glyph::{Font, OutlineCurve, ScaleFont};
+    let font = export_font();
+    let scaled = font.as_scaled(export_font_scale(font_size));
+    let (sx, sy) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+    let (ascent, row_height) = text_metrics(font_size);
 
-        // Keyboard shortcuts
-        ctx.input(|i| {
-            if i.modifiers.ctrl && i.key_pressed(egui::Key::Z) {
+    let mut pb = tiny_skia::PathBuilder::new();
+    for (row, line) in content.split('\n').enumerate() {
+        let baseline = pos.1 + row as f32 * row_height + ascent;
+        let mut caret = pos.0;
+        let mut last = None;
+        for ch in line.chars() {
+            let id = scaled.glyph_id(ch);
+            if let Some(prev) = last {
+                caret += scaled.kern(prev, id);
+            }
+            let origin = caret;
+            caret += scaled.h_advance(id);
+            last = Some(id);
+            let Some(outline) = font.outline(id) else {
+                continue;
+            };
+            // Font units are y-up; each curve carries its own start point,
+            // and a new contour begins wherever it doesn't continue the
+            // previous one.
+            let map = |p: ab_glyph::Point| (origin + p.x * sx, baseline - p.y * sy);
+            let mut pen: Option<ab_glyph::Point> = None;
+            for curve in &outline.curves {
+                let (start, end) = match curve {
+                    OutlineCurve::Line(a, b) => (*a, *b),
+                    OutlineCurve::Quad(a, _, b) => (*a, *b),
+                    OutlineCurve::Cubic(a, _, _, b) => (*a, *b),
+                };
+                if pen != Some(start) {
+                    if pen.is_some() {
+                        pb.close();
+                    }
+                    let (x, y) = map(start);
+                    pb.move_to(x, y);
+                }
+                match curve {
+                    OutlineCurve::Line(_, b) => {
+                        let (x, y) = map(*b);
+                        pb.line_to(x, y);
+                    }
+                    OutlineCurve::Quad(_, c, b) => {
+                        let (cx, cy) = map(*c);
+                        let (x, y) = map(*b);
+                        pb.quad_to(cx, cy, x, y);
+                    }
+                    OutlineCurve::Cubic(_, c1, c2, b) => {
+                        let (c1x, c1y) = map(*c1);
+                        let (c2x, c2y) = map(*c2);
+                        let (x, y) = map(*b);
+                        pb.cubic_to(c1x, c1y, c2x, c2y, x, y);
+                    }
+                }
+                pen = Some(end);
+            }
+            if pen.is_some() {
+                pb.close();
+            }
+        }
+    }
+    pb.finish()
+}
+
+// ── Redaction ───────────────────────────────────────────────────────────────
+//
+// Pixelate and redact fills rewrite the source image's pixels before any
+// export reads them, so the original pixels under them never reach an output
+// file, not even hidden underneath an overlay the way a PDF's blur patches
+// are.
+
+/// The smallest pixelation block. A block of one pixel averages nothing,
+/// so it would leave the image as it was.
+const MIN_PIXELATE_BLOCK: u32 = 2;
+
+fn deserialize_pixelate_block<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let block = u32::deserialize(deserializer)?;
+    if block < MIN_PIXELATE_BLOCK {
+        return Err(serde::de::Error::custom(format!(
+            "pixelate block size {block} is below the minimum of {MIN_PIXELATE_BLOCK}"
+        )));
+    }
+    Ok(block)
+}
+
+/// How a `ScenePrim::Redaction` destroys the pixels it covers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Redaction {
+    /// Each block of this many pixels, aligned to the image grid, becomes
+    /// the average of its covered pixels.
+    Pixelate(u32),
+    /// Every covered pixel becomes opaque black.
+    Solid,
+}
+
+/// The pixels a redaction covers, within its bounds clamped to the image.
+struct RedactionArea {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    covered: Vec<bool>,
+}
+
+impl RedactionArea {
+    /// Whether the image pixel at `(x, y)` is covered.
+    fn covers(&self, x: u32, y: u32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width
+            && y < self.y + self.height
+            && self.covered[((y - self.y) * self.width + (x - self.x)) as usize]
+    }
+
+    /// The image-grid-aligned blocks of `size` pixels overlapping the
+    /// area, as `(x0, y0, x1, y1)` image-space pixel ranges clamped to it.
+    fn blocks(&self, size: u32) -> Vec<(u32, u32, u32, u32)> {
+        let size = size.max(1);
+        let (right, bottom) = (self.x + self.width, self.y + self.height);
+        let mut blocks = Vec::new();
+        for by in (self.y - self.y % size..bottom).step_by(size as usize) {
+            for bx in (self.x - self.x % size..right).step_by(size as usize) {
+                blocks.push((
+                    bx.max(self.x),
+                    by.max(self.y),
+                    (bx + size).min(right),
+                    (by + size).min(bottom),
+                ));
+            }
+        }
+        blocks
+    }
+}
+
+/// Finds the pixels of an image of `image_size` that a fill with these
+/// bounds covers. Any pixel the region touches at all counts, so that no
+/// anti-aliased edge pixel keeps a blend of the original.
+fn redaction_area(
+    image_size: (u32, u32),
+    min: (f32, f32),
+    max: (f32, f32),
+    mask: &FillMask,
+) -> Option<RedactionArea> {
+    let path = fill_mask_path(min, max, mask)?;
+    // Half a pixel of slack on every side, so slivers too thin for the
+    // anti-aliasing to register still count.
+    let outline = path.stroke(
+        &tiny_skia::Stroke {
+            width: 1.0,
+            line_join: tiny_skia::LineJoin::Round,
+            ..Default::default()
+        },
+        1.0,
+    );
+    let bounds = outline.as_ref().unwrap_or(&path).bounds();
+    let clamp = |v: f32, limit: u32| (v.max(0.0) as u32).min(limit);
+    let (x, y) = (clamp(bounds.left().floor(), image_size.0), clamp(bounds.top().floor(), image_size.1));
+    let right = clamp(bounds.right().ceil(), image_size.0);
+    let bottom = clamp(bounds.bottom().ceil(), image_size.1);
+    let (width, height) = (right.checked_sub(x)?, bottom.checked_sub(y)?);
+    let mut coverage = tiny_skia::Mask::new(width, height)?;
+    let shift = tiny_skia::Transform::from_translate(-(x as f32), -(y as f32));
+    coverage.fill_path(&path, tiny_skia::FillRule::Winding, true, shift);
+    if let Some(outline) = outline {
+        coverage.fill_path(&outline, tiny_skia::FillRule::Winding, true, shift);
+    }
+    Some(RedactionArea {
+        x,
+        y,
+        width,
+        height,
+        covered: coverage.data().iter().map(|&c| c > 0).collect(),
+    })
+}
+
+/// Crops `base` to the area and applies `effect` to the covered pixels of
+/// the crop, leaving the others as they were.
+fn redacted_patch<V>(base: &V, area: &RedactionArea, effect: Redaction) -> RgbaImage
+where
+    V: image::GenericImageView<Pixel = image::Rgba<u8>> + 'static,
+{
+    let mut patch = imageops::crop_imm(base, area.x, area.y, area.width, area.height).to_image();
+    let covered = |x: u32, y: u32| area.covers(x, y);
+    match effect {
+        Redaction::Solid => {
+            for (px, py, pixel) in patch.enumerate_pixels_mut() {
+                if covered(area.x + px, area.y + py) {
+                    *pixel = image::Rgba([0, 0, 0, 255]);
+                }
+            }
+        }
+        Redaction::Pixelate(size) => {
+            for (x0, y0, x1, y1) in area.blocks(size) {
+                let pixels: Vec<(u32, u32)> = (y0..y1)
+                    .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                    .filter(|&(x, y)| covered(x, y))
+                    .collect();
+                if pixels.is_empty() {
+                    continue;
+                }
+                let mut sum = [0u64; 4];
+                for &(x, y) in &pixels {
+                    let pixel = patch.get_pixel(x - area.x, y - area.y);
+                    for (total, channel) in sum.iter_mut().zip(pixel.0) {
+                        *total += u64::from(channel);
+                    }
+                }
+                let n = pixels.len() as u64;
+                let average = image::Rgba(sum.map(|total| ((total + n / 2) / n) as u8));
+                for (x, y) in pixels {
+                    patch.put_pixel(x - area.x, y - area.y, average);
+                }
+            }
+        }
+    }
+    patch
+}
+
+/// One pixelate or redact fill, as `ScenePrim::Redaction` gives it.
+#[derive(Clone, Debug, PartialEq)]
+struct RedactionRegion {
+    min: (f32, f32),
+    max: (f32, f32),
+    effect: Redaction,
+    mask: FillMask,
+}
+
+/// The annotations' pixelate and redact fills, in annotation order.
+fn redaction_regions(annotations: &[Annotation]) -> Vec<RedactionRegion> {
+    annotations
+        .iter()
+        .flat_map(|ann| annotation_prims(&ann.kind, None))
+        .filter_map(|prim| match prim {
+            ScenePrim::Redaction {
+                min,
+                max,
+                effect,
+                mask,
+            } => Some(RedactionRegion { min, max, effect, mask }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The source image with every pixelate and redact fill applied, in
+/// annotation order, and checked with `verify_redactions`. Every export
+/// draws on this instead of the original.
+fn redact_source<'a>(
+    raw: &'a DynamicImage,
+    annotations: &[Annotation],
+) -> Result<std::borrow::Cow<'a, DynamicImage>, String> {
+    let size = (raw.width(), raw.height());
+    let redactions: Vec<(RedactionArea, Redaction)> = redaction_regions(annotations)
+        .into_iter()
+        .filter_map(|region| {
+            redaction_area(size, region.min, region.max, &region.mask).map(|area| (area, region.effect))
+        })
+        .collect();
+    if redactions.is_empty() {
+        return Ok(std::borrow::Cow::Borrowed(raw));
+    }
+    let original = raw.to_rgba8();
+    let mut img = original.clone();
+    for (area, effect) in &redactions {
+        let patch = redacted_patch(&img, area, *effect);
+        for (px, py, pixel) in patch.enumerate_pixels() {
+            if area.covers(area.x + px, area.y + py) {
+                img.put_pixel(area.x + px, area.y + py, *pixel);
+            }
+        }
+    }
+    verify_redactions(&original, &img, &redactions)?;
+    Ok(std::borrow::Cow::Owned(DynamicImage::ImageRgba8(img)))
+}
+
+/// Checks, independently of how they were applied, that every pixel the
+/// redactions cover carries nothing but what its redaction leaves there:
+/// opaque black, or one value shared by its whole pixelation block of at
+/// least `MIN_PIXELATE_BLOCK` pixels. Pixels covered again by a later
+/// redaction are checked against that one. A redaction whose pixels all
+/// still match `original`, where those differed from each other, did
+/// nothing and fails too.
+fn verify_redactions(
+    original: &RgbaImage,
+    img: &RgbaImage,
+    redactions: &[(RedactionArea, Redaction)],
+) -> Result<(), String> {
+    let mut checked = vec![false; img.width() as usize * img.height() as usize];
+    for (area, effect) in redactions.iter().rev() {
+        let size = match effect {
+            Redaction::Pixelate(size) if *size < MIN_PIXELATE_BLOCK => {
+                return Err(format!("pixelate block size {size} hides nothing"));
+            }
+            Redaction::Pixelate(size) => *size,
+            Redaction::Solid => 1,
+        };
+        let mut first_original = None;
+        let (mut varied, mut unchanged) = (false, true);
+        for (x0, y0, x1, y1) in area.blocks(size) {
+            let mut expected = match effect {
+                Redaction::Pixelate(_) => None,
+                Redaction::Solid => Some(image::Rgba([0, 0, 0, 255])),
+            };
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let index = y as usize * img.width() as usize + x as usize;
+                    if !area.covers(x, y) || checked[index] {
+                        continue;
+                    }
+                    let pixel = *img.get_pixel(x, y);
+                    if *expected.get_or_insert(pixel) != pixel {
+                        return Err(format!("redacted pixel ({x}, {y}) still carries image data"));
+                    }
+                    let before = *original.get_pixel(x, y);
+                    varied |= *first_original.get_or_insert(before) != before;
+                    unchanged &= before == pixel;
+                }
+            }
+        }
+        if varied && unchanged {
+            return Err(format!(
+                "the redaction at ({}, {}) left the image unchanged",
+                area.x, area.y
+            ));
+        }
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                if area.covers(x, y) {
+                    checked[y as usize * img.width() as usize + x as usize] = true;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether any annotation pixelates or redacts part of the image.
+fn has_redactions(annotations: &[Annotation]) -> bool {
+    !redaction_regions(annotations).is_empty()
+}
+
+/// Checks that an encoded raster holds nothing but the image: no EXIF,
+/// XMP, text or timestamp metadata that could say where a screenshot came
+/// from. The encoder never copies any from the source, this makes sure.
+fn verify_no_metadata(format: ExportFormat, data: &[u8]) -> Result<(), String> {
+    let found = |name: &[u8]| {
+        Err(format!(
+            "{} output contains a {:?} metadata block",
+            format.extension(),
+            String::from_utf8_lossy(name)
+        ))
+    };
+    match format {
+        ExportFormat::Png => {
+            // Chunks: length, type, data, CRC.
+            let mut rest = data.get(8..).unwrap_or_default();
+            while rest.len() >= 12 {
+                let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+                let name = &rest[4..8];
+                if !matches!(
+                    name,
+                    b"IHDR" | b"PLTE" | b"tRNS" | b"IDAT" | b"IEND" | b"gAMA" | b"cHRM" | b"sRGB" | b"sBIT" | b"pHYs"
+                ) {
+                    return found(name);
+                }
+                rest = rest.get(12 + len..).unwrap_or_default();
+            }
+        }
+        ExportFormat::Jpeg => {
+            // Marker segments up to the scan data: 0xFF, marker, length.
+            let mut rest = data.get(2..).unwrap_or_default();
+            while rest.len() >= 4 && rest[0] == 0xFF && rest[1] != 0xDA {
+                let marker = rest[1];
+                let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+                let payload = rest.get(4..2 + len).unwrap_or_default();
+                let allowed = match marker {
+                    0xE0 => payload.starts_with(b"JFIF\0"),
+                    0xE1..=0xEF | 0xFE => false,
+                    _ => true,
+                };
+                if !allowed {
+                    return found(payload.split(|&b| b == 0).next().unwrap_or_default());
+                }
+                rest = rest.get(2 + len..).unwrap_or_default();
+            }
+        }
+        ExportFormat::Webp => {
+            // RIFF chunks after the "RIFF", size, "WEBP" header: type,
+            // little-endian length, data padded to an even length.
+            let mut rest = data.get(12..).unwrap_or_default();
+            while rest.len() >= 8 {
+                let name = &rest[0..4];
+                let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+                if !matches!(name, b"VP8 " | b"VP8L" | b"VP8X" | b"ALPH") {
+                    return found(name);
+                }
+                rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+            }
+        }
+        ExportFormat::Svg | ExportFormat::Pdf => {}
+    }
+    Ok(())
+}
+
+// ── Spotlight ───────────────────────────────────────────────────────────────
+
+/// How much a new spotlight dims the rest of the image.
+const DEFAULT_SPOTLIGHT_DIM: f32 = 0.6;
+
+/// The image's spotlights, merged: everything outside the `holes` is
+/// dimmed by the strongest `dim` any of them asks for, and grayed when any
+/// asks for it.
+#[derive(Clone, Debug, PartialEq)]
+struct Spotlight {
+    dim: f32,
+    grayscale: bool,
+    holes: Vec<SpotlightHole>,
+}
+
+/// One lit region: the part of the bounds `mask` covers.
+#[derive(Clone, Debug, PartialEq)]
+struct SpotlightHole {
+    min: (f32, f32),
+    max: (f32, f32),
+    mask: FillMask,
+}
+
+/// The canvas's copy of what `export_source` makes of the image.
+struct CanvasSource {
+    /// The redactions and spotlight it was made for.
+    key: SourceKey,
+    /// `None` when redacting failed.
+    image: Option<RgbaImage>,
+    /// `None` when redacting failed, or when `key` is plain and the image
+    /// texture already shows it.
+    texture: Option<egui::TextureHandle>,
+}
+
+/// What `export_source` makes of the image: the redactions and spotlight
+/// it applies. The canvas redraws its copy only when this changes.
+#[derive(Clone, Debug, PartialEq)]
+struct SourceKey {
+    redactions: Vec<RedactionRegion>,
+    spotlight: Option<Spotlight>,
+}
+
+impl SourceKey {
+    fn new(annotations: &[Annotation]) -> Self {
+        Self {
+            redactions: redaction_regions(annotations),
+            spotlight: spotlight(annotations),
+        }
+    }
+
+    /// Whether `export_source` leaves the image as it is.
+    fn is_plain(&self) -> bool {
+        self.redactions.is_empty() && self.spotlight.is_none()
+    }
+}
+
+/// The spotlight the annotations ask for, if any has a spotlight fill.
+fn spotlight(annotations: &[Annotation]) -> Option<Spotlight> {
+    annotations
+        .iter()
+        .flat_map(|ann| annotation_prims(&ann.kind, None))
+        .fold(None, |merged, prim| {
+            let ScenePrim::Spotlight {
+                min,
+                max,
+                dim,
+                grayscale,
+                mask,
+            } = prim
+            else {
+                return merged;
+            };
+            let mut merged = merged.unwrap_or(Spotlight {
+                dim: 0.0,
+                grayscale: false,
+                holes: Vec::new(),
+            });
+            merged.dim = merged.dim.max(dim);
+            merged.grayscale |= grayscale;
+            merged.holes.push(SpotlightHole { min, max, mask });
+            Some(merged)
+        })
+}
+
+/// Dims (and maybe grays) `img` outside the spotlight's holes, blending
+/// across their anti-aliased edges.
+fn apply_spotlight(img: &mut RgbaImage, spotlight: &Spotlight) {
+    let Some(mut lit) = tiny_skia::Mask::new(img.width(), img.height()) else {
+        return;
+    };
+    for hole in &spotlight.holes {
+        if let Some(path) = fill_mask_path(hole.min, hole.max, &hole.mask) {
+            lit.fill_path(&path, tiny_skia::FillRule::Winding, true, tiny_skia::Transform::identity());
+        }
+    }
+    let keep = 1.0 - spotlight.dim.clamp(0.0, 1.0);
+    for (pixel, &coverage) in img.pixels_mut().zip(lit.data()) {
+        let coverage = coverage as f32 / 255.0;
+        let [r, g, b, _] = pixel.0.map(|c| c as f32);
+        let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        for (c, v) in pixel.0.iter_mut().zip([r, g, b]) {
+            let dimmed = if spotlight.grayscale { luma } else { v } * keep;
+            *c = (v * coverage + dimmed * (1.0 - coverage)).round() as u8;
+        }
+    }
+}
+
+// ── Export ──────────────────────────────────────────────────────────────────
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Svg,
+    Pdf,
+}
+
+impl ExportFormat {
+    /// Parses a `--format` value or a file extension.
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            "svg" => Some(Self::Svg),
+            "pdf" => Some(Self::Pdf),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+fn annotated_path(image_path: &Path, extension: &str) -> PathBuf {
+    image_path.with_file_name(format!(
+        "{}_annotated.{}",
+        image_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or("out"),
+        extension
+    ))
+}
+
+/// Encodes the annotated image as the bytes of a `format` file.
+fn render_export(
+    format: ExportFormat,
+    raw: &DynamicImage,
+    annotations: &[Annotation],
+    calibration: Option<&Calibration>,
+    svg_image: &SvgImageRef,
+) -> Result<Vec<u8>, String> {
+    let raster_format = match format {
+        ExportFormat::Svg => {
+            return render_svg(raw, annotations, calibration, svg_image).map(String::into_bytes);
+        }
+        ExportFormat::Pdf => return render_pdf(&[(raw, annotations, calibration)]),
+        ExportFormat::Png => image::ImageFormat::Png,
+        ExportFormat::Jpeg => image::ImageFormat::Jpeg,
+        ExportFormat::Webp => image::ImageFormat::WebP,
+    };
+    let img = DynamicImage::ImageRgba8(render_annotated(raw, annotations, calibration)?);
+    // JPEG has no alpha channel.
+    let img = if format == ExportFormat::Jpeg {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    } else {
+        img
+    };
+    let mut out = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut out, raster_format)
+        .map_err(|e| e.to_string())?;
+    let data = out.into_inner();
+    verify_no_metadata(format, &data)?;
+    Ok(data)
+}
+
+// ── SVG Export ──────────────────────────────────────────────────────────────
+
+/// How the exported SVG refers to the source image.
+#[derive(Clone, Debug, PartialEq)]
+enum SvgImageRef {
+    /// Inline the image as a base64 PNG data URI; the SVG is self-contained.
+    Embedded,
+    /// Link to the image by this href, relative to the SVG's location.
+    Linked(String),
+}
+
+/// The path of `target` relative to the directory holding `from`, as a URL
+/// path usable as an href in that file.
+fn relative_href(target: &Path, from: &Path) -> String {
+    use std::path::Component;
+    let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+    let target = absolute(target);
+    let base = absolute(from.parent().unwrap_or(Path::new(".")));
+    let target: Vec<Component> = target.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = target
+        .iter()
+        .zip(&base)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts: Vec<String> = vec!["..".to_string(); base.len() - common];
+    parts.extend(
+        target[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts
+        .join("/")
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('#', "%23")
+        .replace('?', "%3F")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `<attr>="rgb(..)" <attr>-opacity=".."`, for `fill` or `stroke`.
+fn svg_paint(attr: &str, color: &Color4) -> String {
+    let [r, g, b, _] = color.to_rgba8();
+    format!(r#"{attr}="rgb({r},{g},{b})" {attr}-opacity="{}""#, color.a)
+}
+
+/// Appends a `<text>` element with one `<tspan>` per line, the first
+/// line's top-left corner at `pos`.
+fn svg_text(body: &mut String, pos: (f32, f32), content: &str, font_size: f32, color: &Color4) {
+    use std::fmt::Write;
+    let (ascent, row_height) = text_metrics(font_size);
+    let _ = write!(
+        body,
+        r#"<text font-family="Ubuntu, sans-serif" font-weight="300" font-size="{font_size}" xml:space="preserve" {}>"#,
+        svg_paint("fill", color),
+    );
+    for (row, line) in content.split('\n').enumerate() {
+        let _ = write!(
+            body,
+            r#"<tspan x="{}" y="{}">{}</tspan>"#,
+            pos.0,
+            pos.1 + ascent + row as f32 * row_height,
+            xml_escape(line)
+        );
+    }
+    body.push_str("</text>\n");
+}
+
+/// An SVG `points` attribute value.
+fn svg_points(points: &[(f32, f32)]) -> String {
+    let points: Vec<String> = points.iter().map(|p| format!("{},{}", p.0, p.1)).collect();
+    points.join(" ")
+}
+
+/// The attributes dashing a stroke, with a leading space, or nothing for a
+/// solid one.
+fn svg_dash(style: StrokeStyle, width: f32) -> String {
+    match style.dash_pattern(width) {
+        None => String::new(),
+        Some([on, off]) => {
+            let cap = if style == StrokeStyle::Dotted {
+                r#" stroke-linecap="round""#
+            } else {
+                ""
+            };
+            format!(r#" stroke-dasharray="{on} {off}"{cap}"#)
+        }
+    }
+}
+
+/// The SVG element drawing an arrow's head.
+fn svg_arrow_head(head: &ArrowHead, color: &Color4, thickness: f32) -> String {
+    match head {
+        ArrowHead::Triangle(points) => {
+            format!(r#"<polygon points="{}" {}/>"#, svg_points(points), svg_paint("fill", color))
+        }
+        ArrowHead::Open(points) => format!(
+            r#"<polyline points="{}" fill="none" stroke-width="{thickness}" stroke-linejoin="miter" {}/>"#,
+            svg_points(points),
+            svg_paint("stroke", color),
+        ),
+        ArrowHead::Dot { center, radius } => format!(
+            r#"<circle cx="{}" cy="{}" r="{radius}" {}/>"#,
+            center.0,
+            center.1,
+            svg_paint("fill", color),
+        ),
+    }
+}
+
+/// The SVG element outlining a rectangle or oval annotation's bounds.
+fn svg_shape(min: (f32, f32), max: (f32, f32), oval: bool) -> String {
+    let (x0, y0) = (min.0.min(max.0), min.1.min(max.1));
+    let (w, h) = ((max.0 - min.0).abs(), (max.1 - min.1).abs());
+    if oval {
+        format!(
+            r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}""#,
+            x0 + w * 0.5,
+            y0 + h * 0.5,
+            w * 0.5,
+            h * 0.5
+        )
+    } else {
+        format!(r#"<rect x="{x0}" y="{y0}" width="{w}" height="{h}""#)
+    }
+}
+
+/// Renders the annotations as native SVG elements over the source image,
+/// so they stay sharp at any scale and remain editable in vector tools.
+/// As on the canvas, `Fill::Blur` blurs the source image only, not the
+/// annotations drawn beneath it. Pixelate and redact fills are baked into
+/// the embedded image, so a linked one is refused when there are any.
+fn render_svg(
+    raw: &DynamicImage,
+    annotations: &[Annotation],
+    calibration: Option<&Calibration>,
+    image_ref: &SvgImageRef,
+) -> Result<String, String> {
+    use base64::Engine;
+    use std::fmt::Write;
+
+    let (w, h) = (raw.width(), raw.height());
+    let href = match image_ref {
+        SvgImageRef::Embedded => {
+            let source = redact_source(raw, annotations)?;
+            let mut png = std::io::Cursor::new(Vec::new());
+            source
+                .write_to(&mut png, image::ImageFormat::Png)
+                .map_err(|e| e.to_string())?;
+            format!(
+                "data:image/png;base64,{}",
+                base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+            )
+        }
+        SvgImageRef::Linked(_) if has_redactions(annotations) => {
+            return Err("a linked source image would not be redacted; embed it instead".to_string());
+        }
+        SvgImageRef::Linked(href) => xml_escape(href),
+    };
+
+    let mut defs = String::new();
+    // Spotlights dim the image itself, so blur fills, which blur `#source`,
+    // see it dimmed, as they do in the raster exports.
+    let image = match spotlight(annotations) {
+        None => format!(r#"<image id="source" x="0" y="0" width="{w}" height="{h}" xlink:href="{href}"/>"#),
+        Some(spot) => {
+            let holes: String = spot
+                .holes
+                .iter()
+                .map(|hole| {
+                    let shape = match &hole.mask {
+                        FillMask::Rect => svg_shape(hole.min, hole.max, false),
+                        FillMask::Oval => svg_shape(hole.min, hole.max, true),
+                        FillMask::Polygon(points) => format!(r#"<polygon points="{}""#, svg_points(points)),
+                    };
+                    format!(r#"{shape} fill="black"/>"#)
+                })
+                .collect();
+            let _ = write!(
+                defs,
+                r#"<mask id="spotlight" maskUnits="userSpaceOnUse" x="0" y="0" width="{w}" height="{h}"><rect width="{w}" height="{h}" fill="white"/>{holes}</mask>"#,
+            );
+            let mut layers =
+                format!(r#"<image id="photo" x="0" y="0" width="{w}" height="{h}" xlink:href="{href}"/>"#);
+            if spot.grayscale {
+                defs.push_str(
+                    r#"<filter id="spotlight-gray" color-interpolation-filters="sRGB"><feColorMatrix type="saturate" values="0"/></filter>"#,
+                );
+                layers.push_str(r##"<use xlink:href="#photo" filter="url(#spotlight-gray)" mask="url(#spotlight)"/>"##);
+            }
+            let _ = write!(
+                layers,
+                r#"<rect width="{w}" height="{h}" fill="black" fill-opacity="{}" mask="url(#spotlight)"/>"#,
+                spot.dim,
+            );
+            format!(r#"<g id="source">{layers}</g>"#)
+        }
+    };
+    let mut body = String::new();
+    for (i, ann) in annotations.iter().enumerate() {
+        match &ann.kind {
+            AnnotationKind::Arrow {
+                start,
+                end,
+                color,
+                thickness,
+                style,
+                heads,
+                head_shape,
+                control,
+            } => {
+                let shaft = match control {
+                    Some(c) => format!(
+                        r#"<path d="M{},{} Q{},{} {},{}" fill="none""#,
+                        start.0, start.1, c.0, c.1, end.0, end.1
+                    ),
+                    None => format!(
+                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}""#,
+                        start.0, start.1, end.0, end.1
+                    ),
+                };
+                let _ = write!(
+                    body,
+                    r#"<g>{shaft} stroke-width="{thickness}" {}{}/>"#,
+                    svg_paint("stroke", color),
+                    svg_dash(*style, *thickness),
+                );
+                for head in arrow_heads(*start, *control, *end, *thickness, *heads, *head_shape) {
+                    body.push_str(&svg_arrow_head(&head, color, *thickness));
+                }
+                body.push_str("</g>\n");
+            }
+            AnnotationKind::Rectangle {
+                min,
+                max,
+                color,
+                thickness,
+                fill,
+                style,
+            }
+            | AnnotationKind::Oval {
+                min,
+                max,
+                color,
+                thickness,
+                fill,
+                style,
+            } => {
+                let oval = matches!(ann.kind, AnnotationKind::Oval { .. });
+                let shape = svg_shape(*min, *max, oval);
+                let fill_attr = match fill {
+                    Fill::Color(fc) => svg_paint("fill", fc),
+                    _ => r#"fill="none""#.to_string(),
+                };
+                if let Fill::Blur(sigma) = fill {
+                    let _ = write!(
+                        defs,
+                        r#"<filter id="blur-{i}" filterUnits="userSpaceOnUse" x="0" y="0" width="{w}" height="{h}"><feGaussianBlur stdDeviation="{sigma}"/></filter><clipPath id="clip-{i}">{shape}/></clipPath>"#,
+                    );
+                    let _ = writeln!(
+                        body,
+                        r##"<g clip-path="url(#clip-{i})"><use xlink:href="#source" filter="url(#blur-{i})"/></g>"##,
+                    );
+                }
+                let _ = writeln!(
+                    body,
+                    r#"{shape} {fill_attr} stroke-width="{thickness}" stroke-linejoin="miter" {}{}/>"#,
+                    svg_paint("stroke", color),
+                    svg_dash(*style, *thickness),
+                );
+            }
+            AnnotationKind::Text {
+                pos,
+                content,
+                font_size,
+                color,
+            } => svg_text(&mut body, *pos, content, *font_size, color),
+            AnnotationKind::Path {
+                points,
+                color,
+                thickness,
+                highlight,
+            } => {
+                let blend = if *highlight {
+                    r#" style="mix-blend-mode:multiply""#
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    body,
+                    r#"<polyline points="{}" fill="none" stroke-width="{thickness}" stroke-linecap="round" stroke-linejoin="round" {}{blend}/>"#,
+                    svg_points(points),
+                    svg_paint("stroke", color),
+                );
+            }
+            AnnotationKind::Polygon {
+                points,
+                color,
+                thickness,
+                fill,
+            } => {
+                let shape = format!(r#"<polygon points="{}""#, svg_points(points));
+                let fill_attr = match fill {
+                    Fill::Color(fc) => svg_paint("fill", fc),
+                    _ => r#"fill="none""#.to_string(),
+                };
+                if let Fill::Blur(sigma) = fill {
+                    let _ = write!(
+                        defs,
+                        r#"<filter id="blur-{i}" filterUnits="userSpaceOnUse" x="0" y="0" width="{w}" height="{h}"><feGaussianBlur stdDeviation="{sigma}"/></filter><clipPath id="clip-{i}">{shape}/></clipPath>"#,
+                    );
+                    let _ = writeln!(
+                        body,
+                        r##"<g clip-path="url(#clip-{i})"><use xlink:href="#source" filter="url(#blur-{i})"/></g>"##,
+                    );
+                }
+                let _ = writeln!(
+                    body,
+                    r#"{shape} {fill_attr} stroke-width="{thickness}" stroke-linejoin="miter" {}/>"#,
+                    svg_paint("stroke", color),
+                );
+            }
+            AnnotationKind::Polyline {
+                points,
+                color,
+                thickness,
+            } => {
+                let _ = writeln!(
+                    body,
+                    r#"<polyline points="{}" fill="none" stroke-width="{thickness}" stroke-linejoin="miter" {}/>"#,
+                    svg_points(points),
+                    svg_paint("stroke", color),
+                );
+            }
+            AnnotationKind::Step {
+                pos,
+                number,
+                color,
+                font_size,
+            } => {
+                let badge = step_badge(*pos, *number, *font_size);
+                let label_color = contrasting_color(color);
+                let _ = writeln!(
+                    body,
+                    r#"<circle cx="{}" cy="{}" r="{}" {} stroke-width="{}" {}/>"#,
+                    pos.0,
+                    pos.1,
+                    badge.radius,
+                    svg_paint("fill", color),
+                    badge.outline,
+                    svg_paint("stroke", &label_color),
+                );
+                svg_text(&mut body, badge.label_pos, &number.to_string(), *font_size, &label_color);
+            }
+            AnnotationKind::Callout {
+                min,
+                max,
+                anchor,
+                content,
+                font_size,
+                background,
+            } => {
+                let layout = callout_layout(*min, *max, content, *font_size);
+                let outline = callout_outline(layout.min, layout.max, *anchor, *font_size);
+                let _ = writeln!(
+                    body,
+                    r#"<polygon points="{}" {}/>"#,
+                    svg_points(&outline),
+                    svg_paint("fill", background),
+                );
+                svg_text(
+                    &mut body,
+                    layout.text_pos,
+                    &layout.wrapped,
+                    *font_size,
+                    &contrasting_color(background),
+                );
+            }
+            AnnotationKind::Magnifier {
+                source_min,
+                source_max,
+                frame,
+                zoom,
+                color,
+                thickness,
+                connector,
+            } => {
+                let source = (
+                    (source_min.0.min(source_max.0), source_min.1.min(source_max.1)),
+                    (source_min.0.max(source_max.0), source_min.1.max(source_max.1)),
+                );
+                let (min, max) = magnifier_frame(*source_min, *source_max, *frame, *zoom);
+                let stroke = format!(r#"fill="none" stroke-width="{thickness}" {}"#, svg_paint("stroke", color));
+                let _ = writeln!(body, "{} {stroke}/>", svg_shape(source.0, source.1, false));
+                let link = if *connector { magnifier_connector(source, (min, max)) } else { None };
+                if let Some((from, to)) = link {
+                    let _ = writeln!(
+                        body,
+                        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {stroke}/>"#,
+                        from.0, from.1, to.0, to.1,
+                    );
+                }
+                // A nested viewport on the source region scales and clips
+                // it in one go.
+                let ((sx0, sy0), (sx1, sy1)) = source;
+                let _ = writeln!(
+                    body,
+                    r##"<svg x="{}" y="{}" width="{}" height="{}" viewBox="{sx0} {sy0} {} {}" preserveAspectRatio="none"><use xlink:href="#source"/></svg>"##,
+                    min.0,
+                    min.1,
+                    max.0 - min.0,
+                    max.1 - min.1,
+                    sx1 - sx0,
+                    sy1 - sy0,
+                );
+                let _ = writeln!(body, "{} {stroke}/>", svg_shape(min, max, false));
+            }
+            AnnotationKind::Dimension {
+                start,
+                end,
+                color,
+                thickness,
+                font_size,
+            } => {
+                let layout = dimension_layout(*start, *end, *thickness, *font_size, calibration);
+                let [(t0, t1), (t2, t3)] = layout.ticks;
+                let _ = writeln!(
+                    body,
+                    r#"<path d="M{},{} L{},{} M{},{} L{},{} M{},{} L{},{}" fill="none" stroke-width="{thickness}" {}/>"#,
+                    start.0,
+                    start.1,
+                    end.0,
+                    end.1,
+                    t0.0,
+                    t0.1,
+                    t1.0,
+                    t1.1,
+                    t2.0,
+                    t2.1,
+                    t3.0,
+                    t3.1,
+                    svg_paint("stroke", color),
+                );
+                let (min, max) = (layout.backdrop_min, layout.backdrop_max);
+                let _ = writeln!(
+                    body,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+                    min.0,
+                    min.1,
+                    max.0 - min.0,
+                    max.1 - min.1,
+                    svg_paint("fill", &dimension_backdrop(color)),
+                );
+                svg_text(&mut body, layout.label_pos, &layout.label, *font_size, color);
+            }
+        }
+    }
+
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{w}" height="{h}" viewBox="0 0 {w} {h}">
+<defs>{defs}</defs>
+{image}
+{body}</svg>
+"#
+    ))
+}
+
+// ── PDF Export ──────────────────────────────────────────────────────────────
+
+/// PDF points per image pixel. Pages are laid out at 96 dpi, the size a
+/// browser shows a screenshot at when zoomed to 100%.
+const PDF_PT_PER_PX: f32 = 0.75;
+
+/// The export font as a PDF embeds it: the glyphs the text uses, each
+/// with the character it shows, under the font resource `id`. It is
+/// written once every page is, by `write_pdf_font`.
+struct PdfFont {
+    id: pdf_writer::Ref,
+    glyphs: std::collections::BTreeMap<u16, char>,
+    /// Characters the font has no glyph for, shown as its missing glyph.
+    missing: BTreeSet<char>,
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, 6)
+}
+
+/// Writes `img` as a flate-compressed RGB image XObject, with a soft mask
+/// carrying its alpha channel when it has any transparent pixels.
+fn write_pdf_image(pdf: &mut pdf_writer::Pdf, alloc: &mut pdf_writer::Ref, img: &RgbaImage) -> pdf_writer::Ref {
+    let id = alloc.bump();
+    let alpha: Vec<u8> = img.pixels().map(|p| p[3]).collect();
+    let mask = if alpha.iter().any(|&a| a < 255) {
+        let mask_id = alloc.bump();
+        let data = deflate(&alpha);
+        let mut mask = pdf.image_xobject(mask_id, &data);
+        mask.filter(pdf_writer::Filter::FlateDecode);
+        mask.width(img.width() as i32);
+        mask.height(img.height() as i32);
+        mask.color_space().device_gray();
+        mask.bits_per_component(8);
+        Some(mask_id)
+    } else {
+        None
+    };
+    let rgb: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let data = deflate(&rgb);
+    let mut image = pdf.image_xobject(id, &data);
+    image.filter(pdf_writer::Filter::FlateDecode);
+    image.width(img.width() as i32);
+    image.height(img.height() as i32);
+    image.color_space().device_rgb();
+    image.bits_per_component(8);
+    if let Some(mask_id) = mask {
+        image.s_mask(mask_id);
+    }
+    id
+}
+
+/// Embeds the export font as a composite font whose character codes are
+/// its glyph ids (Identity-H), so PDF text uses the canvas glyphs for any
+/// character the font has. A ToUnicode map keeps the text selectable and
+/// searchable.
+fn write_pdf_font(pdf: &mut pdf_writer::Pdf, alloc: &mut pdf_writer::Ref, used: &PdfFont) {
+    use ab_glyph::Font;
+    use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+    use pdf_writer::{Filter, Finish, Name, Rect, Str};
+
+    let font = export_font();
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    let to_glyph_space = |v: f32| v * 1000.0 / units_per_em;
+    let cid_font_id = alloc.bump();
+    let descriptor_id = alloc.bump();
+    let file_id = alloc.bump();
+    let cmap_id = alloc.bump();
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+
+    pdf.type0_font(used.id)
+        .base_font(Name(b"Ubuntu-Light"))
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_font_id)
+        .to_unicode(cmap_id);
+
+    let widths: Vec<(u16, f32)> = used
+        .glyphs
+        .keys()
+        .map(|&id| (id, to_glyph_space(font.h_advance_unscaled(ab_glyph::GlyphId(id)))))
+        .collect();
+    let mut cid_font = pdf.cid_font(cid_font_id);
+    cid_font
+        .subtype(CidFontType::Type2)
+        .base_font(Name(b"Ubuntu-Light"))
+        .system_info(system_info)
+        .font_descriptor(descriptor_id)
+        .cid_to_gid_map_predefined(Name(b"Identity"));
+    let mut cid_widths = cid_font.widths();
+    for &(id, width) in &widths {
+        cid_widths.consecutive(id, [width]);
+    }
+    cid_widths.finish();
+    cid_font.finish();
+
+    let ascent = to_glyph_space(font.ascent_unscaled());
+    let descent = to_glyph_space(font.descent_unscaled());
+    let cap_height = font
+        .outline(font.glyph_id('H'))
+        .map_or(ascent, |o| to_glyph_space(o.bounds.max.y));
+    let max_width = widths.iter().map(|&(_, width)| width).fold(0.0, f32::max);
+    pdf.font_descriptor(descriptor_id)
+        .name(Name(b"Ubuntu-Light"))
+        .flags(FontFlags::SYMBOLIC)
+        .bbox(Rect::new(0.0, descent, max_width, ascent))
+        .italic_angle(0.0)
+        .ascent(ascent)
+        .descent(descent)
+        .cap_height(cap_height)
+        .stem_v(80.0)
+        .font_file2(file_id);
+
+    let data = deflate(epaint_default_fonts::UBUNTU_LIGHT);
+    pdf.stream(file_id, &data)
+        .filter(Filter::FlateDecode)
+        .pair(Name(b"Length1"), epaint_default_fonts::UBUNTU_LIGHT.len() as i32);
+
+    let mut cmap = UnicodeCmap::new(Name(b"Ubuntu-Light-UTF16"), system_info);
+    for (&id, &ch) in &used.glyphs {
+        cmap.pair(id, ch);
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+}
+
+/// Accumulates a page's content stream together with the resources it
+/// refers to by name.
+struct PdfPageBuilder<'a> {
+    pdf: &'a mut pdf_writer::Pdf,
+    alloc: &'a mut pdf_writer::Ref,
+    font: &'a mut Option<PdfFont>,
+    content: pdf_writer::Content,
+    x_objects: Vec<(String, pdf_writer::Ref)>,
+    /// Graphics state resource names, by stroke and fill opacity (in
+    /// 255ths) and whether they multiply, so each combination is written
+    /// once per page.
+    ext_states: std::collections::BTreeMap<(u8, u8, bool), String>,
+}
+
+impl PdfPageBuilder<'_> {
+    /// Draws `img` with its top-left corner at (x, y) in image space.
+    fn draw_image(&mut self, img: &RgbaImage, x: f32, y: f32) {
+        self.draw_image_scaled(img, (x, y), (img.width() as f32, img.height() as f32));
+    }
+
+    /// Draws `img` stretched to `size`, with its top-left corner at `pos`.
+    fn draw_image_scaled(&mut self, img: &RgbaImage, pos: (f32, f32), size: (f32, f32)) {
+        let id = write_pdf_image(self.pdf, self.alloc, img);
+        let name = format!("Im{}", self.x_objects.len());
+        let ((x, y), (w, h)) = (pos, size);
+        self.content.save_state();
+        // Image XObjects fill the unit square bottom-up; flip them back
+        // into the page's y-down image space.
+        self.content.transform([w, 0.0, 0.0, -h, x, y + h]);
+        self.content.x_object(pdf_writer::Name(name.as_bytes()));
+        self.content.restore_state();
+        self.x_objects.push((name, id));
+    }
+
+    fn set_alpha(&mut self, stroke: f32, fill: f32) {
+        self.set_ext_state(stroke, fill, false);
+    }
+
+    /// Sets the stroke and fill opacity, and the multiply blend mode when
+    /// `multiply` is set.
+    fn set_ext_state(&mut self, stroke: f32, fill: f32, multiply: bool) {
+        if stroke >= 1.0 && fill >= 1.0 && !multiply {
+            return;
+        }
+        let quantize = |alpha: f32| (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let count = self.ext_states.len();
+        let name = self
+            .ext_states
+            .entry((quantize(stroke), quantize(fill), multiply))
+            .or_insert_with(|| format!("GS{count}"));
+        self.content.set_parameters(pdf_writer::Name(name.as_bytes()));
+    }
+
+    fn set_stroke(&mut self, stroke: &PrimStroke) {
+        self.content
+            .set_stroke_rgb(stroke.color.r, stroke.color.g, stroke.color.b)
+            // pdf-writer panics on a negative width.
+            .set_line_width(stroke.width.max(0.0));
+        if let Some(pattern) = stroke.style.dash_pattern(stroke.width) {
+            self.content.set_dash_pattern(pattern, 0.0);
+        }
+        if stroke.style == StrokeStyle::Dotted {
+            self.content.set_line_cap(pdf_writer::types::LineCapStyle::RoundCap);
+        }
+    }
+
+    /// Paints the path just built with whichever of fill and stroke are
+    /// present; their colors must already be set.
+    fn paint_path(&mut self, fill: &Option<Color4>, stroke: &Option<PrimStroke>) {
+        match (fill, stroke) {
+            (Some(_), Some(_)) => self.content.fill_nonzero_and_stroke(),
+            (Some(_), None) => self.content.fill_nonzero(),
+            (None, Some(_)) => self.content.stroke(),
+            (None, None) => self.content.end_path(),
+        };
+    }
+
+    fn set_fill_and_stroke(&mut self, fill: &Option<Color4>, stroke: &Option<PrimStroke>) {
+        self.set_alpha(
+            stroke.as_ref().map_or(1.0, |s| s.color.a),
+            fill.as_ref().map_or(1.0, |f| f.a),
+        );
+        if let Some(fc) = fill {
+            self.content.set_fill_rgb(fc.r, fc.g, fc.b);
+        }
+        if let Some(s) = stroke {
+            self.set_stroke(s);
+        }
+    }
+
+    /// Draws a scene primitive as vector paths and text. Blurs are baked
+    /// into a raster patch computed from `source`, like on the canvas.
+    fn draw_prim(&mut self, prim: &ScenePrim, source: &RgbaImage) {
+        self.content.save_state();
+        match prim {
+            ScenePrim::Line {
+                from,
+                to,
+                color,
+                width,
+            } => {
+                self.set_alpha(color.a, 1.0);
+                self.set_stroke(&PrimStroke {
+                    width: *width,
+                    color: color.clone(),
+                    style: StrokeStyle::Solid,
+                });
+                self.content.move_to(from.0, from.1).line_to(to.0, to.1).stroke();
+            }
+            ScenePrim::Path {
+                points,
+                stroke,
+                multiply,
+            } => {
+                if let Some((first, rest)) = points.split_first() {
+                    self.set_ext_state(stroke.color.a, 1.0, *multiply);
+                    self.set_stroke(stroke);
+                    self.content
+                        .set_line_cap(pdf_writer::types::LineCapStyle::RoundCap)
+                        .set_line_join(pdf_writer::types::LineJoinStyle::RoundJoin);
+                    self.content.move_to(first.0, first.1);
+                    for p in rest {
+                        self.content.line_to(p.0, p.1);
+                    }
+                    self.content.stroke();
+                }
+            }
+            ScenePrim::Polyline {
+                points,
+                closed,
+                fill,
+                stroke,
+            } => {
+                if let Some((first, rest)) = points.split_first() {
+                    self.set_fill_and_stroke(fill, stroke);
+                    self.content.move_to(first.0, first.1);
+                    for p in rest {
+                        self.content.line_to(p.0, p.1);
+                    }
+                    if *closed {
+                        self.content.close_path();
+                        self.paint_path(fill, stroke);
+                    } else {
+                        self.paint_path(&None, stroke);
+                    }
+                }
+            }
+            ScenePrim::Polygon { points, color } => {
+                if let Some((first, rest)) = points.split_first() {
+                    self.set_alpha(1.0, color.a);
+                    self.content.set_fill_rgb(color.r, color.g, color.b);
+                    self.content.move_to(first.0, first.1);
+                    for p in rest {
+                        self.content.line_to(p.0, p.1);
+                    }
+                    self.content.close_path().fill_nonzero();
+                }
+            }
+            ScenePrim::Rect {
+                min,
+                max,
+                fill,
+                stroke,
+            } => {
+                self.set_fill_and_stroke(fill, stroke);
+                self.content.rect(
+                    min.0.min(max.0),
+                    min.1.min(max.1),
+                    (max.0 - min.0).abs(),
+                    (max.1 - min.1).abs(),
+                );
+                self.paint_path(fill, stroke);
+            }
+            ScenePrim::Ellipse {
+                center,
+                radii,
+                fill,
+                stroke,
+            } => {
+                // Four cubic arcs; KAPPA places the control points so each
+                // quarter stays within 0.03% of a true ellipse.
+                const KAPPA: f32 = 0.552_284_8;
+                let (cx, cy) = *center;
+                let (rx, ry) = *radii;
+                let (ox, oy) = (rx * KAPPA, ry * KAPPA);
+                self.set_fill_and_stroke(fill, stroke);
+                self.content
+                    .move_to(cx + rx, cy)
+                    .cubic_to(cx + rx, cy + oy, cx + ox, cy + ry, cx, cy + ry)
+                    .cubic_to(cx - ox, cy + ry, cx - rx, cy + oy, cx - rx, cy)
+                    .cubic_to(cx - rx, cy - oy, cx - ox, cy - ry, cx, cy - ry)
+                    .cubic_to(cx + ox, cy - ry, cx + rx, cy - oy, cx + rx, cy)
+                    .close_path();
+                self.paint_path(fill, stroke);
+            }
+            ScenePrim::Blur {
+                min,
+                max,
+                sigma,
+                mask,
+            } => {
+                if let Some((ox, oy, patch)) =
+                    blurred_patch(source, min.0, min.1, max.0, max.1, *sigma, mask)
+                {
+                    self.draw_image(&patch, ox as f32, oy as f32);
+                }
+            }
+            // Already applied to `source` by `redact_source` and
+            // `apply_spotlight`.
+            ScenePrim::Redaction { .. } | ScenePrim::Spotlight { .. } => {}
+            ScenePrim::Inset {
+                src_min,
+                src_max,
+                min,
+                max,
+            } => {
+                let size = (source.width() as f32, source.height() as f32);
+                if let Some([s0, s1, d0, d1]) = clip_inset(*src_min, *src_max, *min, *max, size) {
+                    // Crop whole pixels, then clip off the parts of the
+                    // edge pixels that lie outside the source.
+                    let (x0, y0) = (s0.0.floor() as u32, s0.1.floor() as u32);
+                    let (x1, y1) = (s1.0.ceil() as u32, s1.1.ceil() as u32);
+                    let crop = imageops::crop_imm(source, x0, y0, x1 - x0, y1 - y0).to_image();
+                    let (sx, sy) = ((d1.0 - d0.0) / (s1.0 - s0.0), (d1.1 - d0.1) / (s1.1 - s0.1));
+                    self.content.rect(d0.0, d0.1, d1.0 - d0.0, d1.1 - d0.1);
+                    self.content.clip_nonzero();
+                    self.content.end_path();
+                    self.draw_image_scaled(
+                        &crop,
+                        (d0.0 - (s0.0 - x0 as f32) * sx, d0.1 - (s0.1 - y0 as f32) * sy),
+                        ((x1 - x0) as f32 * sx, (y1 - y0) as f32 * sy),
+                    );
+                }
+            }
+            ScenePrim::Text {
+                pos,
+                content,
+                font_size,
+                color,
+            } => {
+                use ab_glyph::Font;
+                let font = export_font();
+                let units_per_em = font.units_per_em().unwrap_or(1000.0);
+                let (ascent, row_height) = text_metrics(*font_size);
+                self.set_alpha(1.0, color.a);
+                self.content.set_fill_rgb(color.r, color.g, color.b);
+                let used = self.font.get_or_insert_with(|| PdfFont {
+                    id: self.alloc.bump(),
+                    glyphs: Default::default(),
+                    missing: BTreeSet::new(),
+                });
+                self.content.begin_text();
+                self.content.set_font(pdf_writer::Name(b"F0"), *font_size);
+                for (row, line) in content.split('\n').enumerate() {
+                    let baseline = pos.1 + ascent + row as f32 * row_height;
+                    // Flip the text back upright within the y-down page.
+                    self.content
+                        .set_text_matrix([1.0, 0.0, 0.0, -1.0, pos.0, baseline]);
+                    let mut shown = self.content.show_positioned();
+                    let mut items = shown.items();
+                    let mut run = Vec::new();
+                    let mut last: Option<ab_glyph::GlyphId> = None;
+                    for ch in line.chars() {
+                        let id = font.glyph_id(ch);
+                        if let Some(prev) = last {
+                            let kern = font.kern_unscaled(prev, id);
+                            if kern != 0.0 {
+                                items.show(pdf_writer::Str(&run));
+                                run.clear();
+                                items.adjust(-kern * 1000.0 / units_per_em);
+                            }
+                        }
+                        last = Some(id);
+                        if id.0 == 0 {
+                            used.missing.insert(ch);
+                        } else {
+                            used.glyphs.entry(id.0).or_insert(ch);
+                        }
+                        run.extend(id.0.to_be_bytes());
+                    }
+                    if !run.is_empty() {
+                        items.show(pdf_writer::Str(&run));
+                    }
+                }
+                self.content.end_text();
+            }
+        }
+        self.content.restore_state();
+    }
+}
+
+/// Renders each image with its annotations onto its own page, the source
+/// raster at the bottom and the annotations as vector paths and embedded
+/// text on top.
+fn render_pdf(pages: &[(&DynamicImage, &[Annotation], Option<&Calibration>)]) -> Result<Vec<u8>, String> {
+    use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref};
+
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let tree_id = alloc.bump();
+    let mut pdf = Pdf::new();
+    let mut font = None;
+    let mut page_ids = Vec::new();
+
+    for (raw, annotations, calibration) in pages {
+        let source = export_source(raw, annotations)?;
+        let (w, h) = (source.width() as f32, source.height() as f32);
+        let mut builder = PdfPageBuilder {
+            pdf: &mut pdf,
+            alloc: &mut alloc,
+            font: &mut font,
+            content: Content::new(),
+            x_objects: Vec::new(),
+            ext_states: Default::default(),
+        };
+        // Everything below is drawn in image space: pixels, y-down.
+        builder.content.save_state();
+        builder
+            .content
+            .transform([PDF_PT_PER_PX, 0.0, 0.0, -PDF_PT_PER_PX, 0.0, h * PDF_PT_PER_PX]);
+        builder.draw_image(&source, 0.0, 0.0);
+        for ann in annotations.iter() {
+            for prim in annotation_prims(&ann.kind, *calibration) {
+                builder.draw_prim(&prim, &source);
+            }
+        }
+        builder.content.restore_state();
+        let PdfPageBuilder {
+            content,
+            x_objects,
+            ext_states,
+            ..
+        } = builder;
+
+        let content_id = alloc.bump();
+        let data = deflate(&content.finish());
+        pdf.stream(content_id, &data).filter(Filter::FlateDecode);
+        let state_ids: Vec<Ref> = ext_states
+            .keys()
+            .map(|&(stroke, fill, multiply)| {
+                let id = alloc.bump();
+                let mut state = pdf.ext_graphics(id);
+                state
+                    .stroking_alpha(f32::from(stroke) / 255.0)
+                    .non_stroking_alpha(f32::from(fill) / 255.0);
+                if multiply {
+                    state.blend_mode(pdf_writer::types::BlendMode::Multiply);
+                }
+                id
+            })
+            .collect();
+
+        let page_id = alloc.bump();
+        let mut page = pdf.page(page_id);
+        page.parent(tree_id);
+        page.media_box(Rect::new(0.0, 0.0, w * PDF_PT_PER_PX, h * PDF_PT_PER_PX));
+        page.contents(content_id);
+        let mut resources = page.resources();
+        let mut x_dict = resources.x_objects();
+        for (name, id) in &x_objects {
+            x_dict.pair(Name(name.as_bytes()), *id);
+        }
+        x_dict.finish();
+        let mut state_dict = resources.ext_g_states();
+        for (name, id) in ext_states.values().zip(&state_ids) {
+            state_dict.pair(Name(name.as_bytes()), *id);
+        }
+        state_dict.finish();
+        if let Some(used) = &font {
+            resources.fonts().pair(Name(b"F0"), used.id);
+        }
+        resources.finish();
+        page.finish();
+        page_ids.push(page_id);
+    }
+
+    if let Some(used) = &font {
+        if !used.missing.is_empty() {
+            let missing: String = used.missing.iter().collect();
+            eprintln!("Warning: the PDF font has no glyphs for {missing:?}, which show as boxes");
+        }
+        write_pdf_font(&mut pdf, &mut alloc, used);
+    }
+    pdf.pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    pdf.catalog(catalog_id).pages(tree_id);
+    Ok(pdf.finish())
+}
+
+// ── eframe App impl ────────────────────────────────────────────────────────
+
+/// A drop-down picking one of `options`. Returns whether it changed.
+fn choice_combo<T: Copy + PartialEq>(
+    ui: &mut egui::Ui,
+    id: &str,
+    value: &mut T,
+    options: &[T],
+    label: fn(T) -> &'static str,
+) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(label(*value))
+        .show_ui(ui, |ui| {
+            for &option in options {
+                changed |= ui.selectable_value(value, option, label(option)).changed();
+            }
+        });
+    changed
+}
+
+impl eframe::App for AnnotateApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.ensure_texture(ctx);
+        self.ensure_source(ctx);
+
+        let widget_focused = ctx.memory(|m| m.focused().is_some());
+        let interacting = ctx.input(|i| i.pointer.any_down()) || widget_focused;
+        if !interacting {
+            self.property_edit_open = false;
+        }
+
+        // Keyboard shortcuts
+        ctx.input(|i| {
+            if i.modifiers.ctrl && i.key_pressed(egui::Key::Z) {
                 if i.modifiers.shift {
                     self.redo();
                 } else {
@@ -928,21 +5787,126 @@ impl eframe::App for AnnotateApp {
             }
             if i.modifiers.ctrl && i.key_pressed(egui::Key::S) {
                 self.auto_save();
-                self.export_annotated();
-            }
-            if i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace) {
-                if self.text_input_pos.is_none() {
-                    if let Some(idx) = self.selected {
-                        if idx < self.annotations.len() {
-                            self.push_undo();
-                            self.annotations.remove(idx);
-                            self.selected = None;
-                            self.auto_save();
+                self.export_annotated(ExportFormat::Png);
+            }
+            // Keys typed into a toolbar field, such as a step's number or
+            // the scale's unit, are that field's.
+            if (i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace))
+                && self.text_input_pos.is_none()
+                && !widget_focused
+            {
+                self.delete_selected();
+            }
+            if !self.vertices.is_empty() {
+                if i.key_pressed(egui::Key::Enter) {
+                    self.finish_poly();
+                } else if i.key_pressed(egui::Key::Escape) {
+                    self.vertices.clear();
+                }
+            }
+        });
+
+        // Clipboard: Ctrl+C / Ctrl+X / Ctrl+V arrive as events, which text
+        // fields get to handle first. egui-winit only turns Ctrl+V into a
+        // Paste event when the clipboard holds text, and swallows the key
+        // press otherwise, so an image-only clipboard is pasted when V is
+        // released instead.
+        if self.text_input_pos.is_none() && ctx.memory(|m| m.focused().is_none()) {
+            let (events, duplicate) = ctx.input(|i| {
+                (
+                    i.events.clone(),
+                    i.modifiers.ctrl && i.key_pressed(egui::Key::D),
+                )
+            });
+            for event in events {
+                match event {
+                    egui::Event::Copy | egui::Event::Cut => {
+                        if let Some(json) = self.copy_selection() {
+                            ctx.copy_text(json);
+                            if matches!(event, egui::Event::Cut) {
+                                self.delete_selected();
+                            }
+                        }
+                    }
+                    egui::Event::Paste(_) if self.image_path.is_none() => {
+                        self.image_paste_tried = true;
+                        self.paste_image(ctx);
+                    }
+                    egui::Event::Paste(text) => self.paste_annotations(&text),
+                    egui::Event::Key {
+                        key: egui::Key::V,
+                        pressed: false,
+                        modifiers,
+                        ..
+                    } if modifiers.command && self.image_path.is_none() => {
+                        let tried_on_press = std::mem::take(&mut self.image_paste_tried);
+                        if !tried_on_press {
+                            self.paste_image(ctx);
                         }
                     }
+                    _ => {}
                 }
             }
-        });
+            if duplicate {
+                self.duplicate_selected();
+            }
+        }
+
+        // Sidecar problems
+        let mut sidecar_action = None;
+        if let Some(problem) = &self.sidecar_problem {
+            egui::TopBottomPanel::top("sidecar_problem").show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    let error_color = ui.visuals().error_fg_color;
+                    match problem {
+                        SidecarProblem::Load {
+                            error,
+                            quarantined,
+                            overwritable,
+                        } => {
+                            ui.colored_label(
+                                error_color,
+                                format!("Annotations not loaded: {error}"),
+                            );
+                            if let Some(copy) = quarantined {
+                                ui.label(format!("A copy was kept at {}.", copy.display()));
+                            }
+                            ui.label("Saving is paused.");
+                            if ui.button("Reload").clicked() {
+                                sidecar_action = Some(SidecarAction::Reload);
+                            }
+                            if *overwritable && ui.button("Overwrite with current annotations").clicked() {
+                                sidecar_action = Some(SidecarAction::Overwrite);
+                            }
+                        }
+                        SidecarProblem::Save(error) => {
+                            ui.colored_label(
+                                error_color,
+                                format!("Annotations not saved: {error}"),
+                            );
+                            if ui.button("Retry").clicked() {
+                                sidecar_action = Some(SidecarAction::Retry);
+                            }
+                            if ui.button("Dismiss").clicked() {
+                                sidecar_action = Some(SidecarAction::Dismiss);
+                            }
+                        }
+                        SidecarProblem::Backup(error) => {
+                            ui.colored_label(
+                                error_color,
+                                format!("Annotations saved but not backed up: {error}"),
+                            );
+                            if ui.button("Dismiss").clicked() {
+                                sidecar_action = Some(SidecarAction::Dismiss);
+                            }
+                        }
+                    }
+                });
+            });
+        }
+        if let Some(action) = sidecar_action {
+            self.handle_sidecar_action(action);
+        }
 
         // Top toolbar
         egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
@@ -951,40 +5915,78 @@ impl eframe::App for AnnotateApp {
                 ui.selectable_value(&mut self.tool, Tool::Rectangle, "Rectangle");
                 ui.selectable_value(&mut self.tool, Tool::Oval, "Oval");
                 ui.selectable_value(&mut self.tool, Tool::Text, "Text");
+                ui.selectable_value(&mut self.tool, Tool::Pen, "Pen");
+                ui.selectable_value(&mut self.tool, Tool::Highlighter, "Highlighter");
+                ui.selectable_value(&mut self.tool, Tool::Polygon, "Polygon");
+                ui.selectable_value(&mut self.tool, Tool::Polyline, "Polyline");
+                ui.selectable_value(&mut self.tool, Tool::Step, "Step");
+                ui.selectable_value(&mut self.tool, Tool::Callout, "Callout");
+                ui.selectable_value(&mut self.tool, Tool::Dimension, "Dimension");
+                ui.selectable_value(&mut self.tool, Tool::Magnifier, "Magnifier");
                 ui.selectable_value(&mut self.tool, Tool::Select, "Select");
+                if !matches!(self.tool, Tool::Polygon | Tool::Polyline) {
+                    self.vertices.clear();
+                }
                 ui.separator();
+                // With the Select tool, these controls edit the selected
+                // annotations directly instead of just setting the defaults
+                // for the next shape drawn. Each control shows the value of
+                // the first selected annotation that has that property.
+                let mut selected_kinds = self.selected_kinds();
+                let has_selection = !selected_kinds.is_empty();
+
                 ui.label("Color:");
-                ui.color_edit_button_rgb(&mut self.color);
-                ui.separator();
-                ui.label("Thickness:");
-                ui.add(egui::Slider::new(&mut self.thickness, 1.0..=20.0));
-                // If a Rectangle/Oval annotation is currently selected, the fill
-                // controls edit that annotation directly instead of just setting
-                // the defaults for the next shape drawn.
-                let selected_fillable = if self.tool == Tool::Select {
-                    self.selected.filter(|&i| {
-                        matches!(
-                            self.annotations.get(i).map(|a| &a.kind),
-                            Some(AnnotationKind::Rectangle { .. }) | Some(AnnotationKind::Oval { .. })
-                        )
-                    })
-                } else {
-                    None
-                };
+                match selected_kinds.first_mut() {
+                    Some(kind) => {
+                        let color = kind.color_mut();
+                        let mut rgb = [color.r, color.g, color.b];
+                        if ui.color_edit_button_rgb(&mut rgb).changed() {
+                            self.edit_selected(|kind| {
+                                let color = kind.color_mut();
+                                [color.r, color.g, color.b] = rgb;
+                            });
+                            self.color = rgb;
+                        }
+                    }
+                    None => {
+                        ui.color_edit_button_rgb(&mut self.color);
+                    }
+                }
 
-                if matches!(self.tool, Tool::Rectangle | Tool::Oval) || selected_fillable.is_some() {
-                    ui.separator();
-                    ui.label("Fill:");
-                    if let Some(idx) = selected_fillable {
-                        let current_fill = match &self.annotations[idx].kind {
-                            AnnotationKind::Rectangle { fill, .. }
-                            | AnnotationKind::Oval { fill, .. } => fill.clone(),
-                            _ => unreachable!(),
-                        };
+                let selected_thickness = selected_kinds
+                    .iter_mut()
+                    .find_map(|kind| kind.thickness_setting());
+                match selected_thickness {
+                    Some(mut thickness) => {
+                        ui.separator();
+                        ui.label("Thickness:");
+                        if ui.add(egui::Slider::new(&mut thickness, MIN_THICKNESS..=MAX_THICKNESS)).changed() {
+                            self.edit_selected(|kind| kind.set_thickness_setting(thickness));
+                            self.thickness = thickness;
+                        }
+                    }
+                    None if has_selection => {}
+                    None => {
+                        ui.separator();
+                        ui.label("Thickness:");
+                        ui.add(egui::Slider::new(&mut self.thickness, MIN_THICKNESS..=MAX_THICKNESS));
+                    }
+                }
+
+                let selected_fill = selected_kinds
+                    .iter_mut()
+                    .find_map(|kind| kind.fill_mut().cloned());
+                match selected_fill {
+                    Some(current_fill) => {
+                        ui.separator();
+                        ui.label("Fill:");
                         let mut mode = match current_fill {
                             Fill::None => FillMode::None,
                             Fill::Color(_) => FillMode::Color,
                             Fill::Blur(_) => FillMode::Blur,
+                            Fill::Pixelate(_) => FillMode::Pixelate,
+                            Fill::Redact => FillMode::Redact,
+                            Fill::Spotlight { .. } => FillMode::Spotlight,
                         };
                         let mut color = match &current_fill {
                             Fill::Color(c) => [c.r, c.g, c.b],
@@ -994,34 +5996,40 @@ impl eframe::App for AnnotateApp {
                             Fill::Blur(s) => s,
                             _ => self.blur_sigma,
                         };
+                        let mut block = match current_fill {
+                            Fill::Pixelate(b) => b,
+                            _ => self.pixelate_block,
+                        };
+                        let (mut dim, mut grayscale) = match current_fill {
+                            Fill::Spotlight { dim, grayscale } => (dim, grayscale),
+                            _ => (self.spotlight_dim, self.spotlight_grayscale),
+                        };
 
-                        let mut should_push_undo = false;
                         let mut changed = false;
-                        if ui.selectable_value(&mut mode, FillMode::None, "None").clicked() {
-                            should_push_undo = true;
-                            changed = true;
-                        }
-                        if ui.selectable_value(&mut mode, FillMode::Color, "Color").clicked() {
-                            should_push_undo = true;
-                            changed = true;
-                        }
-                        if ui.selectable_value(&mut mode, FillMode::Blur, "Blur").clicked() {
-                            should_push_undo = true;
-                            changed = true;
-                        }
+                        changed |= ui.selectable_value(&mut mode, FillMode::None, "None").changed();
+                        changed |= ui.selectable_value(&mut mode, FillMode::Color, "Color").changed();
+                        changed |= ui.selectable_value(&mut mode, FillMode::Blur, "Blur").changed();
+                        changed |= ui.selectable_value(&mut mode, FillMode::Pixelate, "Pixelate").changed();
+                        changed |= ui.selectable_value(&mut mode, FillMode::Redact, "Redact").changed();
+                        changed |= ui.selectable_value(&mut mode, FillMode::Spotlight, "Spotlight").changed();
                         match mode {
                             FillMode::Color => {
-                                let resp = ui.color_edit_button_rgb(&mut color);
-                                should_push_undo |= resp.drag_started();
-                                changed |= resp.changed();
+                                changed |= ui.color_edit_button_rgb(&mut color).changed();
                             }
                             FillMode::Blur => {
                                 ui.label("Amount:");
-                                let resp = ui.add(egui::Slider::new(&mut sigma, 1.0..=40.0));
-                                should_push_undo |= resp.drag_started();
-                                changed |= resp.changed();
+                                changed |= ui.add(egui::Slider::new(&mut sigma, MIN_BLUR_SIGMA..=MAX_BLUR_SIGMA)).changed();
+                            }
+                            FillMode::Pixelate => {
+                                ui.label("Block:");
+                                changed |= ui.add(egui::Slider::new(&mut block, MIN_PIXELATE_BLOCK..=64)).changed();
+                            }
+                            FillMode::Spotlight => {
+                                ui.label("Dim:");
+                                changed |= ui.add(egui::Slider::new(&mut dim, 0.1..=0.9)).changed();
+                                changed |= ui.checkbox(&mut grayscale, "Gray").changed();
                             }
-                            FillMode::None => {}
+                            FillMode::None | FillMode::Redact => {}
                         }
 
                         if changed {
@@ -1034,42 +6042,225 @@ impl eframe::App for AnnotateApp {
                                     a: 1.0,
                                 }),
                                 FillMode::Blur => Fill::Blur(sigma),
+                                FillMode::Pixelate => Fill::Pixelate(block),
+                                FillMode::Redact => Fill::Redact,
+                                FillMode::Spotlight => Fill::Spotlight { dim, grayscale },
                             };
-                            if should_push_undo {
-                                self.push_undo();
-                            }
-                            if let Some(ann) = self.annotations.get_mut(idx) {
-                                match &mut ann.kind {
-                                    AnnotationKind::Rectangle { fill, .. }
-                                    | AnnotationKind::Oval { fill, .. } => *fill = new_fill,
-                                    _ => {}
+                            self.edit_selected(|kind| {
+                                if let Some(fill) = kind.fill_mut() {
+                                    *fill = new_fill.clone();
                                 }
-                            }
-                            self.auto_save();
+                            });
                             self.fill_mode = mode;
                             self.fill_color = color;
                             self.blur_sigma = sigma;
+                            self.pixelate_block = block;
+                            self.spotlight_dim = dim;
+                            self.spotlight_grayscale = grayscale;
                         }
-                    } else {
+                    }
+                    None if !has_selection && matches!(self.tool, Tool::Rectangle | Tool::Oval | Tool::Polygon) => {
+                        ui.separator();
+                        ui.label("Fill:");
                         ui.selectable_value(&mut self.fill_mode, FillMode::None, "None");
                         ui.selectable_value(&mut self.fill_mode, FillMode::Color, "Color");
                         ui.selectable_value(&mut self.fill_mode, FillMode::Blur, "Blur");
+                        ui.selectable_value(&mut self.fill_mode, FillMode::Pixelate, "Pixelate");
+                        ui.selectable_value(&mut self.fill_mode, FillMode::Redact, "Redact");
+                        ui.selectable_value(&mut self.fill_mode, FillMode::Spotlight, "Spotlight");
                         match self.fill_mode {
                             FillMode::Color => {
                                 ui.color_edit_button_rgb(&mut self.fill_color);
                             }
                             FillMode::Blur => {
                                 ui.label("Amount:");
-                                ui.add(egui::Slider::new(&mut self.blur_sigma, 1.0..=40.0));
+                                ui.add(egui::Slider::new(&mut self.blur_sigma, MIN_BLUR_SIGMA..=MAX_BLUR_SIGMA));
+                            }
+                            FillMode::Pixelate => {
+                                ui.label("Block:");
+                                ui.add(egui::Slider::new(&mut self.pixelate_block, MIN_PIXELATE_BLOCK..=64));
                             }
-                            FillMode::None => {}
+                            FillMode::Spotlight => {
+                                ui.label("Dim:");
+                                ui.add(egui::Slider::new(&mut self.spotlight_dim, 0.1..=0.9));
+                                ui.checkbox(&mut self.spotlight_grayscale, "Gray");
+                            }
+                            FillMode::None | FillMode::Redact => {}
+                        }
+                    }
+                    None => {}
+                }
+
+                let selected_style = selected_kinds
+                    .iter_mut()
+                    .find_map(|kind| kind.style_mut().copied());
+                match selected_style {
+                    Some(mut style) => {
+                        ui.separator();
+                        ui.label("Line:");
+                        if choice_combo(ui, "line-style", &mut style, &StrokeStyle::ALL, StrokeStyle::label) {
+                            self.edit_selected(|kind| {
+                                if let Some(s) = kind.style_mut() {
+                                    *s = style;
+                                }
+                            });
+                            self.stroke_style = style;
                         }
                     }
+                    None if !has_selection && matches!(self.tool, Tool::Arrow | Tool::Rectangle | Tool::Oval) => {
+                        ui.separator();
+                        ui.label("Line:");
+                        choice_combo(
+                            ui,
+                            "line-style",
+                            &mut self.stroke_style,
+                            &StrokeStyle::ALL,
+                            StrokeStyle::label,
+                        );
+                    }
+                    None => {}
                 }
-                if self.tool == Tool::Text {
+
+                let selected_heads = selected_kinds
+                    .iter_mut()
+                    .find_map(|kind| kind.arrow_heads_mut().map(|(heads, shape)| (*heads, *shape)));
+                match selected_heads {
+                    Some((mut heads, mut shape)) => {
+                        ui.separator();
+                        ui.label("Heads:");
+                        let mut changed = choice_combo(ui, "arrow-heads", &mut heads, &ArrowHeads::ALL, ArrowHeads::label);
+                        changed |= choice_combo(ui, "head-shape", &mut shape, &HeadShape::ALL, HeadShape::label);
+                        if changed {
+                            self.edit_selected(|kind| {
+                                if let Some((h, s)) = kind.arrow_heads_mut() {
+                                    (*h, *s) = (heads, shape);
+                                }
+                            });
+                            self.arrow_heads = heads;
+                            self.head_shape = shape;
+                        }
+                    }
+                    None if !has_selection && self.tool == Tool::Arrow => {
+                        ui.separator();
+                        ui.label("Heads:");
+                        choice_combo(ui, "arrow-heads", &mut self.arrow_heads, &ArrowHeads::ALL, ArrowHeads::label);
+                        choice_combo(ui, "head-shape", &mut self.head_shape, &HeadShape::ALL, HeadShape::label);
+                    }
+                    None => {}
+                }
+
+                let curved_arrow = matches!(
+                    selected_kinds.as_slice(),
+                    [AnnotationKind::Arrow { control: Some(_), .. }]
+                );
+                if curved_arrow && ui.button("Straighten").clicked() {
+                    self.edit_selected(|kind| {
+                        if let AnnotationKind::Arrow { control, .. } = kind {
+                            *control = None;
+                        }
+                    });
+                }
+
+                let selected_font = selected_kinds
+                    .iter_mut()
+                    .find_map(|kind| kind.font_size_mut().copied());
+                if let Some(mut font_size) = selected_font {
                     ui.separator();
                     ui.label("Font:");
-                    ui.add(egui::Slider::new(&mut self.font_size, 8.0..=72.0));
+                    if ui.add(egui::Slider::new(&mut font_size, MIN_FONT_SIZE..=MAX_FONT_SIZE)).changed() {
+                        self.edit_selected(|kind| {
+                            if let Some(f) = kind.font_size_mut() {
+                                *f = font_size;
+                            }
+                        });
+                        self.font_size = font_size;
+                    }
+                } else if !has_selection && matches!(self.tool, Tool::Text | Tool::Step | Tool::Callout | Tool::Dimension) {
+                    ui.separator();
+                    ui.label("Font:");
+                    ui.add(egui::Slider::new(&mut self.font_size, MIN_FONT_SIZE..=MAX_FONT_SIZE));
+                }
+
+                // Giving a step another number moves it in the sequence.
+                let selected_step = match (self.single_selection(), selected_kinds.as_slice()) {
+                    (Some(index), [AnnotationKind::Step { number, .. }]) => Some((index, *number)),
+                    _ => None,
+                };
+                if let Some((index, old)) = selected_step {
+                    let last = next_step_number(&self.annotations) - 1;
+                    let mut number = old;
+                    ui.separator();
+                    ui.label("Step:");
+                    if ui.add(egui::DragValue::new(&mut number).range(1..=last)).changed() {
+                        self.edit_selected(|kind| {
+                            if let AnnotationKind::Step { number: n, .. } = kind {
+                                *n = number;
+                            }
+                        });
+                        renumber_steps(&mut self.annotations, Some((index, old)));
+                        self.auto_save();
+                    }
+                }
+
+                let selected_magnifier = match selected_kinds.as_slice() {
+                    [AnnotationKind::Magnifier { zoom, connector, .. }] => Some((*zoom, *connector)),
+                    _ => None,
+                };
+                match selected_magnifier {
+                    Some((mut zoom, mut connector)) => {
+                        ui.separator();
+                        ui.label("Magnify:");
+                        let mut changed = ui
+                            .add(egui::Slider::new(&mut zoom, MIN_MAGNIFIER_ZOOM..=MAX_MAGNIFIER_ZOOM))
+                            .changed();
+                        changed |= ui.checkbox(&mut connector, "Connector").changed();
+                        if changed {
+                            self.edit_selected(|kind| {
+                                if let AnnotationKind::Magnifier { zoom: z, connector: c, .. } = kind {
+                                    (*z, *c) = (zoom, connector);
+                                }
+                            });
+                            self.magnify = zoom;
+                            self.magnifier_connector = connector;
+                        }
+                    }
+                    None if !has_selection && self.tool == Tool::Magnifier => {
+                        ui.separator();
+                        ui.label("Magnify:");
+                        ui.add(egui::Slider::new(&mut self.magnify, MIN_MAGNIFIER_ZOOM..=MAX_MAGNIFIER_ZOOM));
+                        ui.checkbox(&mut self.magnifier_connector, "Connector");
+                    }
+                    None => {}
+                }
+
+                // A dimension across a span of known size sets the scale
+                // every dimension label is shown in.
+                let selected_span = match selected_kinds.as_slice() {
+                    [AnnotationKind::Dimension { start, end, .. }] => {
+                        Some((end.0 - start.0).hypot(end.1 - start.1))
+                    }
+                    _ => None,
+                };
+                if let Some(pixels) = selected_span {
+                    ui.separator();
+                    ui.label("Measures:");
+                    ui.add(egui::DragValue::new(&mut self.scale_length).range(0.001..=f32::MAX).speed(0.1));
+                    ui.add(egui::TextEdit::singleline(&mut self.scale_unit).desired_width(32.0));
+                    if ui
+                        .add_enabled(pixels > 0.0, egui::Button::new("Set scale"))
+                        .clicked()
+                    {
+                        self.calibration = Some(Calibration {
+                            pixels,
+                            length: self.scale_length,
+                            unit: self.scale_unit.trim().to_string(),
+                        });
+                        self.auto_save();
+                    }
+                    if self.calibration.is_some() && ui.button("Clear scale").clicked() {
+                        self.calibration = None;
+                        self.auto_save();
+                    }
                 }
                 ui.separator();
                 if ui.button("Undo").clicked() {
@@ -1079,12 +6270,35 @@ impl eframe::App for AnnotateApp {
                     self.redo();
                 }
                 ui.separator();
+                if ui.button("Export SVG").clicked() {
+                    self.auto_save();
+                    self.export_annotated(ExportFormat::Svg);
+                }
+                if ui.button("Export PDF").clicked() {
+                    self.auto_save();
+                    self.export_annotated(ExportFormat::Pdf);
+                }
+                if ui.button("Copy to clipboard").clicked() {
+                    self.copy_export_to_clipboard();
+                }
+                ui.separator();
                 ui.label(format!("Zoom: {:.0}%", self.zoom * 100.0));
             });
         });
 
         // Canvas
         egui::CentralPanel::default().show(ctx, |ui| {
+            if self.image_path.is_none() {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(ui.available_height() * 0.4);
+                    ui.label("No image open.");
+                    if ui.button("Paste image from clipboard").clicked() {
+                        self.paste_image(ctx);
+                    }
+                });
+                return;
+            }
+
             let (response, painter) = ui.allocate_painter(
                 ui.available_size(),
                 egui::Sense::click_and_drag(),
@@ -1095,106 +6309,99 @@ impl eframe::App for AnnotateApp {
             painter.rect_filled(canvas_rect, 0.0, egui::Color32::from_gray(40));
 
             // Draw image
-            if let Some(ref tex) = self.texture {
-                let img_rect = self.image_rect_on_screen(canvas_rect);
-                painter.image(
-                    tex.id(),
-                    img_rect,
-                    egui::Rect::from_min_max(
-                        egui::pos2(0.0, 0.0),
-                        egui::pos2(1.0, 1.0),
-                    ),
-                    egui::Color32::WHITE,
-                );
+            let wanted = SourceKey::new(&self.annotations);
+            let stale = self.raw_image.is_some() && !self.source_is_current();
+            let current = self
+                .source
+                .as_ref()
+                .filter(|_| !stale)
+                .and_then(|source| source.texture.as_ref());
+            match (current, &self.texture) {
+                (Some(tex), _) => {
+                    painter.image(
+                        tex.id(),
+                        self.image_rect_on_screen(canvas_rect),
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        egui::Color32::WHITE,
+                    );
+                }
+                (_, Some(tex)) => match &wanted.spotlight {
+                    Some(spot) => self.paint_spotlight_preview(&painter, canvas_rect, tex, spot),
+                    None => {
+                        painter.image(
+                            tex.id(),
+                            self.image_rect_on_screen(canvas_rect),
+                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                            egui::Color32::WHITE,
+                        );
+                    }
+                },
+                (_, None) => {}
+            }
+            if stale {
+                // Another frame lets `ensure_source` catch up once released.
+                ctx.request_repaint();
             }
 
             // Draw annotations
             self.draw_annotations(ctx, &painter, canvas_rect);
 
-            // Draw in-progress annotation preview
-            if let DragState::Drawing { start } = self.drag {
+            // Draw in-progress annotation preview
+            if let DragState::Drawing { start } = self.drag {
+                if let Some(current) = response.hover_pos() {
+                    let img_start = self.screen_to_image(canvas_rect, start);
+                    let img_end = self.screen_to_image(canvas_rect, current);
+                    if let Some(kind) = self.new_shape(img_start, img_end) {
+                        self.paint_prims(ctx, &painter, canvas_rect, &annotation_prims(&kind, self.calibration.as_ref()));
+                    }
+                }
+            }
+            if let Some(kind) = &self.callout_draft {
+                self.paint_prims(ctx, &painter, canvas_rect, &annotation_prims(kind, self.calibration.as_ref()));
+            }
+            if let DragState::Sketching { points } = &self.drag {
+                if let Some(kind) = self.new_path(points) {
+                    self.paint_prims(ctx, &painter, canvas_rect, &annotation_prims(&kind, self.calibration.as_ref()));
+                }
+            }
+            if let Some(first) = self.vertices.first() {
+                let mut points = self.vertices.clone();
+                if let Some(current) = response.hover_pos() {
+                    points.push(self.screen_to_image(canvas_rect, current));
+                }
+                match self.new_poly(&points) {
+                    Some(kind) => {
+                        self.paint_prims(ctx, &painter, canvas_rect, &annotation_prims(&kind, self.calibration.as_ref()));
+                    }
+                    None => {
+                        let screen: Vec<egui::Pos2> = points
+                            .iter()
+                            .map(|p| self.image_to_screen(canvas_rect, *p))
+                            .collect();
+                        painter.add(egui::Shape::line(
+                            screen,
+                            egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 120, 255)),
+                        ));
+                    }
+                }
+                painter.circle(
+                    self.image_to_screen(canvas_rect, *first),
+                    HANDLE_RADIUS,
+                    egui::Color32::WHITE,
+                    egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 120, 255)),
+                );
+            }
+
+            // Rubber-band selection
+            if let DragState::Marquee { start } = self.drag {
                 if let Some(current) = response.hover_pos() {
-                    let c = self.current_color4().to_egui();
-                    let t = self.thickness * self.zoom;
-                    match self.tool {
-                        Tool::Arrow => {
-                            painter.line_segment(
-                                [start, current],
-                                egui::Stroke::new(t, c),
-                            );
-                            let dir = (current - start).normalized();
-                            let head_len = (t * 4.0).max(10.0);
-                            let perp = egui::vec2(-dir.y, dir.x);
-                            let p1 =
-                                current - dir * head_len + perp * head_len * 0.4;
-                            let p2 =
-                                current - dir * head_len - perp * head_len * 0.4;
-                            painter.add(egui::Shape::convex_polygon(
-                                vec![current, p1, p2],
-                                c,
-                                egui::Stroke::NONE,
-                            ));
-                        }
-                        Tool::Rectangle => {
-                            let rect = egui::Rect::from_two_pos(start, current);
-                            if self.fill_mode == FillMode::Blur {
-                                let img_start = self.screen_to_image(canvas_rect, start);
-                                let img_end = self.screen_to_image(canvas_rect, current);
-                                self.draw_blur_fill(
-                                    ctx,
-                                    &painter,
-                                    canvas_rect,
-                                    ((img_start.x, img_start.y), (img_end.x, img_end.y)),
-                                    self.blur_sigma,
-                                    false,
-                                );
-                                painter.rect_stroke(rect, 0.0, egui::Stroke::new(t, c), egui::StrokeKind::Middle);
-                            } else {
-                                let fill = if self.fill_mode == FillMode::Color {
-                                    egui::Color32::from_rgba_unmultiplied(
-                                        (self.fill_color[0] * 255.0) as u8,
-                                        (self.fill_color[1] * 255.0) as u8,
-                                        (self.fill_color[2] * 255.0) as u8,
-                                        255,
-                                    )
-                                } else {
-                                    egui::Color32::TRANSPARENT
-                                };
-                                painter.rect(rect, 0.0, fill, egui::Stroke::new(t, c), egui::StrokeKind::Middle);
-                            }
-                        }
-                        Tool::Oval => {
-                            let rect = egui::Rect::from_two_pos(start, current);
-                            let center = rect.center();
-                            let radii = rect.size() * 0.5;
-                            if self.fill_mode == FillMode::Blur {
-                                let img_start = self.screen_to_image(canvas_rect, start);
-                                let img_end = self.screen_to_image(canvas_rect, current);
-                                self.draw_blur_fill(
-                                    ctx,
-                                    &painter,
-                                    canvas_rect,
-                                    ((img_start.x, img_start.y), (img_end.x, img_end.y)),
-                                    self.blur_sigma,
-                                    true,
-                                );
-                                painter.add(egui::epaint::EllipseShape { center, radius: radii, fill: egui::Color32::TRANSPARENT, stroke: egui::Stroke::new(t, c) });
-                            } else {
-                                let fill = if self.fill_mode == FillMode::Color {
-                                    egui::Color32::from_rgba_unmultiplied(
-                                        (self.fill_color[0] * 255.0) as u8,
-                                        (self.fill_color[1] * 255.0) as u8,
-                                        (self.fill_color[2] * 255.0) as u8,
-                                        255,
-                                    )
-                                } else {
-                                    egui::Color32::TRANSPARENT
-                                };
-                                painter.add(egui::epaint::EllipseShape { center, radius: radii, fill, stroke: egui::Stroke::new(t, c) });
-                            }
-                        }
-                        _ => {}
-                    }
+                    painter.rect(
+                        egui::Rect::from_two_pos(start, current),
+                        0.0,
+                        egui::Color32::from_rgba_unmultiplied(0, 120, 255, 30),
+                        egui::Stroke::new(1.0, egui::Color32::from_rgb(0, 120, 255)),
+                        egui::StrokeKind::Middle,
+                    );
                 }
             }
 
@@ -1211,19 +6418,31 @@ impl eframe::App for AnnotateApp {
                     ui.set_max_width(300.0);
                     let te = ui.text_edit_singleline(&mut self.text_input_buf);
                     if te.lost_focus() {
-                        if !self.text_input_buf.is_empty() {
-                            self.push_undo();
-                            self.annotations.push(Annotation {
-                                kind: AnnotationKind::Text {
-                                    pos: img_pos,
-                                    content: self.text_input_buf.clone(),
-                                    font_size: self.font_size,
-                                    color: self.current_color4(),
-                                },
-                            });
-                            self.auto_save();
+                        let content = std::mem::take(&mut self.text_input_buf);
+                        match self.text_editing.take() {
+                            Some(index) => self.commit_text_edit(index, content),
+                            None if !content.is_empty() => {
+                                let kind = match self.callout_draft.take() {
+                                    Some(mut draft) => {
+                                        if let Some(text) = draft.content_mut() {
+                                            *text = content;
+                                        }
+                                        draft
+                                    }
+                                    None => AnnotationKind::Text {
+                                        pos: img_pos,
+                                        content,
+                                        font_size: self.font_size,
+                                        color: self.current_color4(),
+                                    },
+                                };
+                                self.push_undo();
+                                self.annotations.push(Annotation { kind });
+                                self.auto_save();
+                            }
+                            None => {}
                         }
-                        self.text_input_buf.clear();
+                        self.callout_draft = None;
                         self.text_input_pos = None;
                     } else {
                         te.request_focus();
@@ -1254,117 +6473,963 @@ impl eframe::App for AnnotateApp {
                 self.zoom = new_zoom;
             }
 
-            // Handle tool interactions (primary button only, not while panning)
-            if !self.panning {
-                if response.drag_started_by(egui::PointerButton::Primary) {
-                    if let Some(pos) = response.hover_pos() {
-                        match self.tool {
-                            Tool::Arrow | Tool::Rectangle | Tool::Oval => {
-                                self.drag = DragState::Drawing { start: pos };
-                            }
-                            Tool::Text => {
-                                let img_pos =
-                                    self.screen_to_image(canvas_rect, pos);
-                                self.text_input_pos =
-                                    Some((img_pos.x, img_pos.y));
-                                self.text_input_buf.clear();
-                            }
-                            Tool::Select => {
-                                if let Some(idx) =
-                                    self.hit_test(canvas_rect, pos)
-                                {
-                                    self.selected = Some(idx);
-                                    self.push_undo();
-                                    self.drag = DragState::Moving {
-                                        index: idx,
-                                    };
-                                } else {
-                                    self.selected = None;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                if response.dragged_by(egui::PointerButton::Primary) {
-                    if let DragState::Moving { index, .. } = &self.drag {
-                        let delta_screen = response.drag_delta();
-                        let delta_img = delta_screen / self.zoom;
-                        self.move_annotation(*index, delta_img);
-                    }
-                }
+            // Resize cursors over the selection's handles
+            if self.tool == Tool::Select && matches!(self.drag, DragState::None) {
+                if let Some(pos) = response.hover_pos() {
+                    if let Some((_, handle)) = self.handle_hit_test(canvas_rect, pos) {
+                        ctx.set_cursor_icon(handle_cursor(handle));
+                    }
+                }
+            }
+
+            // Double-clicking a Text or Callout annotation re-opens it for
+            // editing
+            if self.tool == Tool::Select && response.double_clicked() && self.text_input_pos.is_none() {
+                if let Some(idx) = response.hover_pos().and_then(|pos| self.hit_test(canvas_rect, pos)) {
+                    let editable = match &self.annotations[idx].kind {
+                        AnnotationKind::Text { pos, content, .. } => Some((*pos, content)),
+                        AnnotationKind::Callout {
+                            min,
+                            max,
+                            content,
+                            font_size,
+                            ..
+                        } => Some((callout_layout(*min, *max, content, *font_size).text_pos, content)),
+                        _ => None,
+                    };
+                    if let Some((pos, content)) = editable {
+                        self.text_input_pos = Some(pos);
+                        self.text_input_buf = content.clone();
+                        self.text_editing = Some(idx);
+                        self.selected = BTreeSet::from([idx]);
+                    }
+                }
+            }
+
+            // Handle tool interactions (primary button only, not while panning)
+            let shift = ctx.input(|i| i.modifiers.shift);
+            if !self.panning {
+                if self.tool == Tool::Select && response.clicked_by(egui::PointerButton::Primary) {
+                    if let Some(pos) = response.hover_pos() {
+                        self.select_at(canvas_rect, pos, shift, false);
+                    }
+                }
+
+                if self.tool == Tool::Step && response.clicked_by(egui::PointerButton::Primary) {
+                    if let Some(pos) = response.hover_pos() {
+                        let pos = self.screen_to_image(canvas_rect, pos);
+                        self.push_undo();
+                        self.annotations.push(Annotation {
+                            kind: AnnotationKind::Step {
+                                pos: (pos.x, pos.y),
+                                number: next_step_number(&self.annotations),
+                                color: self.current_color4(),
+                                font_size: self.font_size,
+                            },
+                        });
+                        self.auto_save();
+                    }
+                }
+
+                // Polygon and Polyline: each click places a vertex; a
+                // double-click, Enter, or clicking the first vertex of a
+                // polygon finishes the shape.
+                if matches!(self.tool, Tool::Polygon | Tool::Polyline) {
+                    if response.double_clicked() {
+                        self.finish_poly();
+                    } else if response.clicked_by(egui::PointerButton::Primary) {
+                        if let Some(pos) = response.hover_pos() {
+                            let closes = self.tool == Tool::Polygon
+                                && self.vertices.len() >= 3
+                                && self.image_to_screen(canvas_rect, self.vertices[0]).distance(pos)
+                                    <= CLOSE_POLYGON_RADIUS;
+                            if closes {
+                                self.finish_poly();
+                            } else {
+                                self.vertices.push(self.screen_to_image(canvas_rect, pos));
+                            }
+                        }
+                    }
+                }
+
+                if response.drag_started_by(egui::PointerButton::Primary) {
+                    if let Some(pos) = response.hover_pos() {
+                        match self.tool {
+                            Tool::Arrow
+                            | Tool::Rectangle
+                            | Tool::Oval
+                            | Tool::Callout
+                            | Tool::Dimension
+                            | Tool::Magnifier => {
+                                self.drag = DragState::Drawing { start: pos };
+                            }
+                            Tool::Pen | Tool::Highlighter => {
+                                self.drag = DragState::Sketching {
+                                    points: vec![self.screen_to_image(canvas_rect, pos)],
+                                };
+                            }
+                            Tool::Text => {
+                                let img_pos =
+                                    self.screen_to_image(canvas_rect, pos);
+                                self.text_input_pos =
+                                    Some((img_pos.x, img_pos.y));
+                                self.text_input_buf.clear();
+                            }
+                            Tool::Polygon | Tool::Polyline | Tool::Step => {}
+                            Tool::Select => {
+                                if let Some((index, handle)) =
+                                    self.handle_hit_test(canvas_rect, pos)
+                                {
+                                    self.push_undo();
+                                    self.drag = DragState::Resizing { index, handle };
+                                } else if let Some(idx) =
+                                    self.select_at(canvas_rect, pos, shift, true)
+                                {
+                                    if self.selected.contains(&idx) {
+                                        self.push_undo();
+                                        self.drag = DragState::Moving;
+                                    }
+                                } else {
+                                    self.drag = DragState::Marquee { start: pos };
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if response.dragged_by(egui::PointerButton::Primary) {
+                    let delta_img = response.drag_delta() / self.zoom;
+                    let hover_img = response
+                        .hover_pos()
+                        .map(|pos| self.screen_to_image(canvas_rect, pos));
+                    if let (DragState::Sketching { points }, Some(p)) = (&mut self.drag, hover_img) {
+                        if points.last() != Some(&p) {
+                            points.push(p);
+                        }
+                    }
+                    match self.drag {
+                        DragState::Moving => {
+                            for index in self.selected.clone() {
+                                self.move_annotation(index, delta_img);
+                            }
+                        }
+                        DragState::Resizing { index, handle } => {
+                            self.resize_annotation(index, handle, delta_img)
+                        }
+                        DragState::None
+                        | DragState::Drawing { .. }
+                        | DragState::Sketching { .. }
+                        | DragState::Marquee { .. } => {}
+                    }
+                }
+
+                if response.drag_stopped_by(egui::PointerButton::Primary) {
+                    match self.drag.clone() {
+                        DragState::Drawing { start } => {
+                            if let Some(end) = response
+                                .hover_pos()
+                                .or(ctx.input(|i| i.pointer.latest_pos()))
+                            {
+                                let img_start =
+                                    self.screen_to_image(canvas_rect, start);
+                                let img_end =
+                                    self.screen_to_image(canvas_rect, end);
+
+                                if (end - start).length() > 5.0 {
+                                    if let Some(kind) = self.new_shape(img_start, img_end) {
+                                        if let AnnotationKind::Callout {
+                                            min, max, font_size, ..
+                                        } = &kind
+                                        {
+                                            // A callout is added once its
+                                            // text has been typed in.
+                                            let layout = callout_layout(*min, *max, "", *font_size);
+                                            self.text_input_pos = Some(layout.text_pos);
+                                            self.text_input_buf.clear();
+                                            self.callout_draft = Some(kind);
+                                        } else {
+                                            self.push_undo();
+                                            self.annotations.push(Annotation { kind });
+                                            self.auto_save();
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        DragState::Sketching { points } => {
+                            let points = simplify_path(&points, PATH_TOLERANCE / self.zoom);
+                            if points.len() >= 2 {
+                                if let Some(kind) = self.new_path(&points) {
+                                    self.push_undo();
+                                    self.annotations.push(Annotation { kind });
+                                    self.auto_save();
+                                }
+                            }
+                        }
+                        DragState::Moving | DragState::Resizing { .. } => {
+                            self.auto_save();
+                        }
+                        DragState::Marquee { start } => {
+                            if let Some(end) = response
+                                .hover_pos()
+                                .or(ctx.input(|i| i.pointer.latest_pos()))
+                            {
+                                let marquee = egui::Rect::from_two_pos(
+                                    self.screen_to_image(canvas_rect, start),
+                                    self.screen_to_image(canvas_rect, end),
+                                );
+                                let hits: Vec<usize> = self
+                                    .annotations
+                                    .iter()
+                                    .enumerate()
+                                    .filter(|(_, ann)| {
+                                        prims_bounds(&annotation_prims(&ann.kind, self.calibration.as_ref())).intersects(marquee)
+                                    })
+                                    .map(|(i, _)| i)
+                                    .collect();
+                                self.selected.extend(hits);
+                            }
+                        }
+                        DragState::None => {}
+                    }
+                    self.drag = DragState::None;
+                }
+            }
+        });
+    }
+}
+
+// ── Main ────────────────────────────────────────────────────────────────────
+
+const DESCRIBE_JSON: &str = r#"{
+  "slug": "annotate_edit",
+  "description": "Open an image file, or the image on the clipboard, for interactive annotation with arrows, shapes, freehand strokes, text and redactions. Annotations are saved as a JSON sidecar file next to the image and exported as PNG, JPEG, WebP, SVG or PDF.",
+  "args": [
+    {
+      "name": "path",
+      "description": "Path to the image file to annotate (PNG or JPEG); without it or clipboard, the editor starts empty",
+      "type": "string",
+      "backing_type": "string",
+      "arity": "optional",
+      "mode": "dashdashspace"
+    },
+    {
+      "name": "clipboard",
+      "description": "Annotate the image on the system clipboard instead of a file",
+      "type": "boolean",
+      "backing_type": "bool",
+      "arity": "flag",
+      "mode": "dashdash"
+    }
+  ],
+  "subcommands": [
+    {
+      "name": "render",
+      "description": "Export an image with the annotations from its sidecar, without opening a window.",
+      "args": [
+        {
+          "name": "path",
+          "description": "Path to the annotated image",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "single",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "out",
+          "description": "Output file; defaults to <name>_annotated.<ext> next to the image",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "format",
+          "description": "Output format: png, jpeg, webp, svg or pdf; defaults to the --out extension, then png",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "link-image",
+          "description": "For svg, reference the source image by path instead of embedding it",
+          "type": "boolean",
+          "backing_type": "bool",
+          "arity": "flag",
+          "mode": "dashdash"
+        }
+      ]
+    },
+    {
+      "name": "apply",
+      "description": "Add annotations to an image's sidecar from arguments or JSON, without opening a window.",
+      "args": [
+        {
+          "name": "path",
+          "description": "Path to the image whose sidecar gets the annotations",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "single",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "color",
+          "description": "Color for the shapes after it, as #rrggbb or #rrggbbaa",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "thickness",
+          "description": "Stroke thickness for the shapes after it",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "font-size",
+          "description": "Font size for the text, steps, callouts and dimensions after it",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "fill",
+          "description": "Fill for the rectangles and ovals after it: none, blur:<n>, pixelate:<n>, redact, spotlight[:<dim>][:gray] or a color",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "style",
+          "description": "Line style for the shapes after it: solid, dashed or dotted",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "heads",
+          "description": "Arrowheads for the arrows after it: none, start, end or both",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "head",
+          "description": "Arrowhead shape for the arrows after it: triangle, open or dot",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "arrow",
+          "description": "Arrow as x0,y0:x1,y1, curved through an optional :cx,cy",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "rect",
+          "description": "Rectangle as x0,y0:x1,y1",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "oval",
+          "description": "Oval inside x0,y0:x1,y1",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "text",
+          "description": "Text as x,y:<text>",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "step",
+          "description": "Numbered step badge at x,y",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "callout",
+          "description": "Callout as x0,y0:x1,y1:ax,ay:<text>, with its tail at ax,ay",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "dimension",
+          "description": "Dimension line as x0,y0:x1,y1",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "magnifier",
+          "description": "Magnifier of the region x0,y0:x1,y1, framed at fx,fy, with an optional :zoom",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "scale",
+          "description": "Calibrated scale for dimension lines, as <pixels>=<length><unit>",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "optional",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "json",
+          "description": "Also read a JSON list of annotation kinds from stdin",
+          "type": "boolean",
+          "backing_type": "bool",
+          "arity": "flag",
+          "mode": "dashdash"
+        }
+      ]
+    },
+    {
+      "name": "pdf",
+      "description": "Export several annotated images as one PDF, one page per image.",
+      "args": [
+        {
+          "name": "out",
+          "description": "Output PDF file",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "single",
+          "mode": "dashdashspace"
+        },
+        {
+          "name": "path",
+          "description": "Path to an annotated image; repeat for each page",
+          "type": "string",
+          "backing_type": "string",
+          "arity": "multiple",
+          "mode": "dashdashspace"
+        }
+      ]
+    }
+  ]
+}"#;
+
+/// The value following `--name` in `args`, if present.
+fn flag_value(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+/// `annotate-edit render --path <image> [--out <file>] [--format <fmt>]
+/// [--link-image]` exports the image with its sidecar's annotations without
+/// opening a window. The format defaults to the `--out` extension, then
+/// PNG; `--link-image` makes SVGs reference the image instead of
+/// embedding it.
+fn run_render(args: &[String]) {
+    const USAGE: &str = "Usage: annotate-edit render --path <image> [--out <file>] [--format png|jpeg|webp|svg|pdf] [--link-image]";
+    let Some(image_path) = flag_value(args, "--path").map(PathBuf::from) else {
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    };
+    let out_arg = flag_value(args, "--out").map(PathBuf::from);
+    let format = match flag_value(args, "--format") {
+        Some(name) => ExportFormat::parse(&name),
+        None => Some(
+            out_arg
+                .as_ref()
+                .and_then(|p| p.extension())
+                .and_then(|ext| ext.to_str())
+                .and_then(ExportFormat::parse)
+                .unwrap_or(ExportFormat::Png),
+        ),
+    };
+    let Some(format) = format else {
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    };
+    let out_path = out_arg.unwrap_or_else(|| annotated_path(&image_path, format.extension()));
+
+    let raw = match image::open(&image_path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            eprintln!("Failed to open {}: {e}", image_path.display());
+            std::process::exit(1);
+        }
+    };
+    let file = load_annotations_or_exit(&image_path);
+    let svg_image = if args.iter().any(|a| a == "--link-image") {
+        SvgImageRef::Linked(relative_href(&image_path, &out_path))
+    } else {
+        SvgImageRef::Embedded
+    };
+    let result = render_export(format, &raw, &file.annotations, file.calibration.as_ref(), &svg_image)
+        .and_then(|data| write_atomic(&out_path, &data).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to export {}: {e}", out_path.display());
+        std::process::exit(1);
+    }
+    eprintln!("Exported to {}", out_path.display());
+}
+
+/// Headless commands refuse to go on with a sidecar they can't read, rather
+/// than rendering (or, for `apply`, overwriting) it as if it were empty.
+fn load_annotations_or_exit(image_path: &Path) -> AnnotationFile {
+    load_annotations(image_path).unwrap_or_else(|e| {
+        eprintln!("Failed to load annotations: {e}");
+        std::process::exit(1);
+    })
+}
+
+fn parse_point(s: &str) -> Result<(f32, f32), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected x,y but got {s:?}"))?;
+    let coord = |v: &str| {
+        v.trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|c| c.is_finite())
+            .ok_or_else(|| format!("invalid coordinate {v:?} in {s:?}"))
+    };
+    Ok((coord(x)?, coord(y)?))
+}
+
+/// Parses `x0,y0:x1,y1`.
+fn parse_span(s: &str) -> Result<[(f32, f32); 2], String> {
+    let (a, b) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected x0,y0:x1,y1 but got {s:?}"))?;
+    Ok([parse_point(a)?, parse_point(b)?])
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`.
+fn parse_color(s: &str) -> Result<Color4, String> {
+    let hex = s.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|h| u8::from_str_radix(h, 16).ok())
+            .map(|v| v as f32 / 255.0)
+    };
+    let parsed = match hex.len() {
+        6 => channel(0).zip(channel(2)).zip(channel(4)).map(|((r, g), b)| (r, g, b, 1.0)),
+        8 => channel(0)
+            .zip(channel(2))
+            .zip(channel(4))
+            .zip(channel(6))
+            .map(|(((r, g), b), a)| (r, g, b, a)),
+        _ => None,
+    };
+    let (r, g, b, a) = parsed.ok_or_else(|| format!("expected #rrggbb or #rrggbbaa but got {s:?}"))?;
+    Ok(Color4 { r, g, b, a })
+}
+
+/// Parses `none`, `blur:<sigma>`, `pixelate:<block>`, `redact` or a color.
+fn parse_fill(s: &str) -> Result<Fill, String> {
+    match s {
+        "none" => return Ok(Fill::None),
+        "redact" => return Ok(Fill::Redact),
+        _ => {}
+    }
+    if s == "spotlight" || s.starts_with("spotlight:") {
+        let (mut dim, mut grayscale) = (DEFAULT_SPOTLIGHT_DIM, false);
+        for part in s.split(':').skip(1) {
+            if part == "gray" {
+                grayscale = true;
+            } else {
+                dim = part
+                    .parse()
+                    .ok()
+                    .filter(|d| (0.0..=1.0).contains(d))
+                    .ok_or_else(|| format!("invalid spotlight dimming {part:?}"))?;
+            }
+        }
+        return Ok(Fill::Spotlight { dim, grayscale });
+    }
+    if let Some(block) = s.strip_prefix("pixelate:") {
+        return block
+            .parse()
+            .ok()
+            .filter(|&b| b >= MIN_PIXELATE_BLOCK)
+            .map(Fill::Pixelate)
+            .ok_or_else(|| format!("invalid pixelate block size {block:?}"));
+    }
+    if let Some(sigma) = s.strip_prefix("blur:") {
+        return sigma
+            .parse()
+            .ok()
+            .filter(|v| (MIN_BLUR_SIGMA..=MAX_BLUR_SIGMA).contains(v))
+            .map(Fill::Blur)
+            .ok_or_else(|| format!("invalid blur amount {sigma:?}"));
+    }
+    parse_color(s).map(Fill::Color)
+}
+
+/// Parses one of `options` by its label, in any case.
+fn parse_choice<T: Copy>(s: &str, options: &[T], label: fn(T) -> &'static str, what: &str) -> Result<T, String> {
+    options
+        .iter()
+        .copied()
+        .find(|&option| label(option).eq_ignore_ascii_case(s))
+        .ok_or_else(|| format!("invalid {what} {s:?}"))
+}
+
+/// Parses `<pixels>=<length><unit>`, such as `200=5cm` for "200 image
+/// pixels measure 5 cm".
+fn parse_calibration(s: &str) -> Result<Calibration, String> {
+    let invalid = || format!("expected <pixels>=<length><unit> but got {s:?}");
+    let (pixels, measured) = s.split_once('=').ok_or_else(invalid)?;
+    let pixels: f32 = pixels.trim().trim_end_matches("px").parse().map_err(|_| invalid())?;
+    let measured = measured.trim();
+    let split = measured
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(measured.len());
+    let length: f32 = measured[..split].parse().map_err(|_| invalid())?;
+    if !(pixels > 0.0 && length > 0.0 && pixels.is_finite() && length.is_finite()) {
+        return Err(format!("the lengths in {s:?} must be positive and finite"));
+    }
+    Ok(Calibration {
+        pixels,
+        length,
+        unit: measured[split..].trim().to_string(),
+    })
+}
+
+/// Parses the annotations `apply --json` reads: a sidecar, or a bare list of
+/// annotation kinds, which is taken as an unversioned sidecar. Either way it
+/// goes through the same migrations as a sidecar on disk.
+fn parse_apply_json(data: &str) -> Result<Vec<AnnotationKind>, String> {
+    let value: serde_json::Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let file = match value {
+        serde_json::Value::Array(kinds) => serde_json::json!({
+            "annotations": kinds
+                .into_iter()
+                .map(|kind| serde_json::json!({ "kind": kind }))
+                .collect::<Vec<_>>(),
+        }),
+        file => file,
+    };
+    let file = parse_annotation_file(&file.to_string()).map_err(|e| match e {
+        SchemaError::Invalid(e) => e.to_string(),
+        SchemaError::TooNew(version) => format!(
+            "format version {version} is newer than this build reads ({ANNOTZ_VERSION})"
+        ),
+    })?;
+    file.annotations.into_iter().map(|ann| check_apply_kind(ann.kind)).collect()
+}
+
+/// Checks an annotation read by `apply --json` against the ranges the
+/// toolbar and the flags allow. A non-finite number would be saved as
+/// `null` and leave the whole sidecar unreadable, and an out-of-range size
+/// can make exports fail or run out of memory.
+fn check_apply_kind(mut kind: AnnotationKind) -> Result<AnnotationKind, String> {
+    let in_range = |value: f32, min: f32, max: f32, what: &str| {
+        if (min..=max).contains(&value) {
+            Ok(())
+        } else {
+            Err(format!("{what} {value} is outside {min} to {max}"))
+        }
+    };
+    let check_color = |c: &Color4| {
+        [c.r, c.g, c.b, c.a]
+            .into_iter()
+            .try_for_each(|channel| in_range(channel, 0.0, 1.0, "color channel"))
+    };
+    if let Some((x, y)) = kind.points().into_iter().find(|(x, y)| !x.is_finite() || !y.is_finite()) {
+        return Err(format!("invalid point {x},{y}"));
+    }
+    check_color(kind.color_mut())?;
+    if let Some(thickness) = kind.thickness_setting() {
+        in_range(thickness, MIN_THICKNESS, MAX_THICKNESS, "thickness")?;
+    }
+    if let Some(&mut font_size) = kind.font_size_mut() {
+        in_range(font_size, MIN_FONT_SIZE, MAX_FONT_SIZE, "font size")?;
+    }
+    match kind.fill_mut() {
+        Some(Fill::Color(color)) => check_color(color)?,
+        Some(&mut Fill::Blur(sigma)) => in_range(sigma, MIN_BLUR_SIGMA, MAX_BLUR_SIGMA, "blur amount")?,
+        Some(&mut Fill::Spotlight { dim, .. }) => in_range(dim, 0.0, 1.0, "spotlight dimming")?,
+        _ => {}
+    }
+    if let AnnotationKind::Magnifier { zoom, .. } = kind {
+        in_range(zoom, MIN_MAGNIFIER_ZOOM, MAX_MAGNIFIER_ZOOM, "zoom")?;
+    }
+    Ok(kind)
+}
+
+/// Builds annotations from `apply` arguments. Style flags apply to every
+/// shape flag after them, so one call can mix styles.
+fn parse_apply_args(args: &[String]) -> Result<Vec<AnnotationKind>, String> {
+    let mut color = Color4::default();
+    let mut thickness = 3.0;
+    let mut font_size = 20.0;
+    let mut fill = Fill::None;
+    let mut style = StrokeStyle::Solid;
+    let mut heads = ArrowHeads::End;
+    let mut head_shape = HeadShape::Triangle;
+    let mut kinds = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        if flag == "--json" {
+            continue;
+        }
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("missing value for {flag}"))?;
+        match flag.as_str() {
+            "--path" | "--scale" => {}
+            "--color" => color = parse_color(value)?,
+            "--thickness" => {
+                thickness = value
+                    .parse()
+                    .ok()
+                    .filter(|t| (MIN_THICKNESS..=MAX_THICKNESS).contains(t))
+                    .ok_or_else(|| format!("invalid thickness {value:?}"))?
+            }
+            "--font-size" => {
+                font_size = value
+                    .parse()
+                    .ok()
+                    .filter(|f| (MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(f))
+                    .ok_or_else(|| format!("invalid font size {value:?}"))?
+            }
+            "--fill" => fill = parse_fill(value)?,
+            "--style" => style = parse_choice(value, &StrokeStyle::ALL, StrokeStyle::label, "line style")?,
+            "--heads" => heads = parse_choice(value, &ArrowHeads::ALL, ArrowHeads::label, "arrow heads")?,
+            "--head" => head_shape = parse_choice(value, &HeadShape::ALL, HeadShape::label, "head shape")?,
+            "--arrow" => {
+                // An optional third point curves the arrow through it.
+                let (span, control) = match value.match_indices(':').nth(1) {
+                    Some((i, _)) => (&value[..i], Some(parse_point(&value[i + 1..])?)),
+                    None => (value.as_str(), None),
+                };
+                let [start, end] = parse_span(span)?;
+                kinds.push(AnnotationKind::Arrow {
+                    start,
+                    end,
+                    color: color.clone(),
+                    thickness,
+                    style,
+                    heads,
+                    head_shape,
+                    control,
+                });
+            }
+            "--rect" => {
+                let [min, max] = parse_span(value)?;
+                kinds.push(AnnotationKind::Rectangle {
+                    min,
+                    max,
+                    color: color.clone(),
+                    thickness,
+                    fill: fill.clone(),
+                    style,
+                });
+            }
+            "--oval" => {
+                let [min, max] = parse_span(value)?;
+                kinds.push(AnnotationKind::Oval {
+                    min,
+                    max,
+                    color: color.clone(),
+                    thickness,
+                    fill: fill.clone(),
+                    style,
+                });
+            }
+            "--text" => {
+                let (pos, content) = value
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected x,y:text but got {value:?}"))?;
+                kinds.push(AnnotationKind::Text {
+                    pos: parse_point(pos)?,
+                    content: content.to_string(),
+                    font_size,
+                    color: color.clone(),
+                });
+            }
+            "--callout" => {
+                let mut parts = value.splitn(4, ':');
+                let (Some(min), Some(max), Some(anchor), Some(content)) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(format!("expected x0,y0:x1,y1:ax,ay:text but got {value:?}"));
+                };
+                kinds.push(AnnotationKind::Callout {
+                    min: parse_point(min)?,
+                    max: parse_point(max)?,
+                    anchor: parse_point(anchor)?,
+                    content: content.to_string(),
+                    font_size,
+                    background: color.clone(),
+                });
+            }
+            "--magnifier" => {
+                let mut parts = value.splitn(4, ':');
+                let (Some(min), Some(max), Some(frame)) = (parts.next(), parts.next(), parts.next()) else {
+                    return Err(format!("expected x0,y0:x1,y1:fx,fy[:zoom] but got {value:?}"));
+                };
+                let zoom = match parts.next() {
+                    Some(zoom) => zoom
+                        .parse()
+                        .ok()
+                        .filter(|z| (MIN_MAGNIFIER_ZOOM..=MAX_MAGNIFIER_ZOOM).contains(z))
+                        .ok_or_else(|| format!("invalid zoom {zoom:?}"))?,
+                    None => 2.0,
+                };
+                kinds.push(AnnotationKind::Magnifier {
+                    source_min: parse_point(min)?,
+                    source_max: parse_point(max)?,
+                    frame: parse_point(frame)?,
+                    zoom,
+                    color: color.clone(),
+                    thickness,
+                    connector: true,
+                });
+            }
+            "--dimension" => {
+                let [start, end] = parse_span(value)?;
+                kinds.push(AnnotationKind::Dimension {
+                    start,
+                    end,
+                    color: color.clone(),
+                    thickness,
+                    font_size,
+                });
+            }
+            // Numbered by `run_apply`, after the sidecar's own steps.
+            "--step" => kinds.push(AnnotationKind::Step {
+                pos: parse_point(value)?,
+                number: 0,
+                color: color.clone(),
+                font_size,
+            }),
+            _ => return Err(format!("unknown argument {flag}")),
+        }
+    }
+    Ok(kinds)
+}
+
+/// `annotate-edit apply --path <image> [shape flags...] [--json]` appends
+/// annotations to the image's sidecar without opening a window. With
+/// `--json`, a JSON list of annotation kinds is also read from stdin.
+fn run_apply(args: &[String]) {
+    const USAGE: &str = "Usage: annotate-edit apply --path <image> [--color #rrggbb[aa]] [--thickness <n>] [--font-size <n>] [--fill none|blur:<n>|pixelate:<n>|redact|spotlight[:<dim>][:gray]|#rrggbb[aa]] [--style solid|dashed|dotted] [--heads none|start|end|both] [--head triangle|open|dot] [--arrow x0,y0:x1,y1[:cx,cy]] [--rect x0,y0:x1,y1] [--oval x0,y0:x1,y1] [--text x,y:<text>] [--step x,y] [--callout x0,y0:x1,y1:ax,ay:<text>] [--dimension x0,y0:x1,y1] [--magnifier x0,y0:x1,y1:fx,fy[:zoom]] [--scale <pixels>=<length><unit>] [--json]";
+    let Some(image_path) = flag_value(args, "--path").map(PathBuf::from) else {
+        eprintln!("{USAGE}");
+        std::process::exit(1);
+    };
+    if !image_path.exists() {
+        eprintln!("File not found: {}", image_path.display());
+        std::process::exit(1);
+    }
+    let mut kinds = match parse_apply_args(args) {
+        Ok(kinds) => kinds,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+    let calibration = match flag_value(args, "--scale").as_deref().map(parse_calibration).transpose() {
+        Ok(calibration) => calibration,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+    if args.iter().any(|a| a == "--json") {
+        let parsed = std::io::read_to_string(std::io::stdin())
+            .map_err(|e| e.to_string())
+            .and_then(|data| parse_apply_json(&data));
+        match parsed {
+            Ok(more) => kinds.extend(more),
+            Err(e) => {
+                eprintln!("Invalid annotations on stdin: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut file = load_annotations_or_exit(&image_path);
+    let mut added: Vec<Annotation> = kinds.into_iter().map(|kind| Annotation { kind }).collect();
+    number_added_steps(&file.annotations, &mut added);
+    let count = added.len();
+    file.annotations.extend(added);
+    if calibration.is_some() {
+        file.calibration = calibration;
+    }
+    match save_annotations(&image_path, &file.annotations, file.calibration.as_ref()) {
+        Ok(Some(backup_error)) => eprintln!("Warning: {backup_error}"),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Failed to save annotations: {e}");
+            std::process::exit(1);
+        }
+    }
+    eprintln!(
+        "Added {count} annotation(s) to {}",
+        annotz_path(&image_path).display()
+    );
+}
 
-                if response.drag_stopped_by(egui::PointerButton::Primary) {
-                    match self.drag.clone() {
-                        DragState::Drawing { start } => {
-                            if let Some(end) = response
-                                .hover_pos()
-                                .or(ctx.input(|i| i.pointer.latest_pos()))
-                            {
-                                let img_start =
-                                    self.screen_to_image(canvas_rect, start);
-                                let img_end =
-                                    self.screen_to_image(canvas_rect, end);
+/// `annotate-edit pdf --out <file> --path <image> [--path <image> ...]`
+/// writes one page per image, each with the annotations from its sidecar.
+fn run_pdf(args: &[String]) {
+    let out_path = flag_value(args, "--out").map(PathBuf::from);
+    let image_paths: Vec<PathBuf> = args
+        .windows(2)
+        .filter(|w| w[0] == "--path")
+        .map(|w| PathBuf::from(&w[1]))
+        .collect();
+    let Some(out_path) = out_path.filter(|_| !image_paths.is_empty()) else {
+        eprintln!("Usage: annotate-edit pdf --out <file> --path <image> [--path <image> ...]");
+        std::process::exit(1);
+    };
 
-                                if (end - start).length() > 5.0 {
-                                    self.push_undo();
-                                    let ann = match self.tool {
-                                        Tool::Arrow => Annotation {
-                                            kind: AnnotationKind::Arrow {
-                                                start: (
-                                                    img_start.x,
-                                                    img_start.y,
-                                                ),
-                                                end: (img_end.x, img_end.y),
-                                                color: self.current_color4(),
-                                                thickness: self.thickness,
-                                            },
-                                        },
-                                        Tool::Rectangle => Annotation {
-                                            kind: AnnotationKind::Rectangle {
-                                                min: (
-                                                    img_start.x,
-                                                    img_start.y,
-                                                ),
-                                                max: (img_end.x, img_end.y),
-                                                color: self.current_color4(),
-                                                thickness: self.thickness,
-                                                fill: self.current_fill(),
-                                            },
-                                        },
-                                        Tool::Oval => Annotation {
-                                            kind: AnnotationKind::Oval {
-                                                min: (
-                                                    img_start.x,
-                                                    img_start.y,
-                                                ),
-                                                max: (img_end.x, img_end.y),
-                                                color: self.current_color4(),
-                                                thickness: self.thickness,
-                                                fill: self.current_fill(),
-                                            },
-                                        },
-                                        _ => unreachable!(),
-                                    };
-                                    self.annotations.push(ann);
-                                    self.auto_save();
-                                }
-                            }
-                        }
-                        DragState::Moving { .. } => {
-                            self.auto_save();
-                        }
-                        DragState::None => {}
-                    }
-                    self.drag = DragState::None;
-                }
+    let mut pages = Vec::new();
+    for path in &image_paths {
+        match image::open(path) {
+            Ok(raw) => pages.push((raw, load_annotations_or_exit(path))),
+            Err(e) => {
+                eprintln!("Failed to open {}: {e}", path.display());
+                std::process::exit(1);
             }
-        });
+        }
+    }
+    let pages: Vec<(&DynamicImage, &[Annotation], Option<&Calibration>)> = pages
+        .iter()
+        .map(|(raw, file)| (raw, file.annotations.as_slice(), file.calibration.as_ref()))
+        .collect();
+    let result = render_pdf(&pages)
+        .and_then(|data| write_atomic(&out_path, &data).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to export {}: {e}", out_path.display());
+        std::process::exit(1);
     }
+    eprintln!("Exported to {}", out_path.display());
 }
 
-// ── Main ────────────────────────────────────────────────────────────────────
-
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
@@ -1372,7 +7437,7 @@ fn main() {
     // single bare argument that isn't a known subcommand is treated the same
     // as `annotate-edit run --path <filepath>`.
     let legacy_path = match args.get(1).map(|s| s.as_str()) {
-        Some("describe") | Some("run") => None,
+        Some("describe") | Some("run") | Some("render") | Some("apply") | Some("pdf") => None,
         Some(_) if args.len() == 2 => Some(args[1].clone()),
         _ => None,
     };
@@ -1380,28 +7445,26 @@ fn main() {
     if legacy_path.is_none() {
         match args.get(1).map(|s| s.as_str()) {
             Some("describe") => {
-                println!(
-                    "{}",
-                    r#"{
-  "slug": "annotate_edit",
-  "description": "Open an image file for interactive annotation with arrows, rectangles, ovals, and text. Annotations are saved as a JSON sidecar file and exported as an annotated PNG.",
-  "args": [
-    {
-      "name": "path",
-      "description": "Path to the image file to annotate (PNG or JPEG)",
-      "type": "string",
-      "backing_type": "string",
-      "arity": "single",
-      "mode": "dashdashspace"
-    }
-  ]
-}"#
-                );
+                println!("{DESCRIBE_JSON}");
+                return;
+            }
+            Some("render") => {
+                run_render(&args[2..]);
+                return;
+            }
+            Some("apply") => {
+                run_apply(&args[2..]);
+                return;
+            }
+            Some("pdf") => {
+                run_pdf(&args[2..]);
                 return;
             }
             Some("run") => {}
             _ => {
-                eprintln!("Usage: annotate-edit <describe|run --path <image>|<image>>");
+                eprintln!(
+                    "Usage: annotate-edit <describe|run [--path <image>|--clipboard]|render --path <image>|apply --path <image>|pdf --out <file> --path <image>...|<image>>"
+                );
                 std::process::exit(1);
             }
         }
@@ -1410,32 +7473,31 @@ fn main() {
     // Parse --path <value> from the remaining args after "run", falling
     // back to the legacy bare-filename form.
     let run_args = &args[2..];
-    let path_value = legacy_path.or_else(|| {
-        run_args
-            .windows(2)
-            .find(|w| w[0] == "--path")
-            .map(|w| w[1].clone())
-    });
-
-    let Some(path_str) = path_value else {
-        eprintln!("Usage: annotate-edit run --path <image>");
-        std::process::exit(1);
-    };
+    let path_value = legacy_path.or_else(|| flag_value(run_args, "--path"));
 
-    let image_path = PathBuf::from(path_str);
-    if !image_path.exists() {
+    // `--clipboard` edits the image on the clipboard; with neither it and
+    // no path, the editor starts empty and waits for an image to be pasted.
+    let image_path = if run_args.iter().any(|a| a == "--clipboard") {
+        let saved = arboard::Clipboard::new()
+            .map_err(|e| e.to_string())
+            .and_then(|mut clipboard| clipboard_image(&mut clipboard))
+            .and_then(|img| save_clipboard_image(&img));
+        match saved {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("Failed to read an image from the clipboard: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        path_value.map(PathBuf::from)
+    };
+    if let Some(image_path) = image_path.as_ref().filter(|path| !path.exists()) {
         eprintln!("File not found: {}", image_path.display());
         std::process::exit(1);
     }
 
-    let title = format!(
-        "annotate-edit — {}",
-        image_path
-            .file_name()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or("")
-    );
+    let title = window_title(image_path.as_deref());
 
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -1451,3 +7513,680 @@ fn main() {
     )
     .expect("Failed to run eframe");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for one test, under the system temp dir.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("annotate-edit-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // ── Sidecar versions ────────────────────────────────────────────────────
+
+    const V0_RECTANGLE: &str = r#"{"annotations": [{"kind": {
+        "type": "Rectangle", "min": [1, 2], "max": [30, 40],
+        "color": {"r": 1, "g": 0, "b": 0, "a": 1}, "thickness": 3
+    }}]}"#;
+
+    #[test]
+    fn unversioned_sidecar_migrates_to_current() {
+        let file = parse_annotation_file(V0_RECTANGLE).unwrap();
+        assert_eq!(file.version, ANNOTZ_VERSION);
+        assert!(matches!(
+            file.annotations[0].kind,
+            AnnotationKind::Rectangle {
+                fill: Fill::None,
+                style: StrokeStyle::Solid,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn every_version_up_to_current_is_read() {
+        for version in 0..=ANNOTZ_VERSION {
+            let data = format!(r#"{{"version": {version}, "annotations": []}}"#);
+            let file = parse_annotation_file(&data).unwrap();
+            assert_eq!(file.version, ANNOTZ_VERSION);
+        }
+    }
+
+    #[test]
+    fn newer_sidecar_is_refused() {
+        let data = format!(r#"{{"version": {}, "annotations": []}}"#, ANNOTZ_VERSION + 1);
+        assert!(matches!(
+            parse_annotation_file(&data),
+            Err(SchemaError::TooNew(v)) if v == ANNOTZ_VERSION as u64 + 1
+        ));
+    }
+
+    #[test]
+    fn invalid_versions_are_refused() {
+        for version in ["-1", "1.5", "\"3\"", "null"] {
+            let data = format!(r#"{{"version": {version}, "annotations": []}}"#);
+            assert!(
+                matches!(parse_annotation_file(&data), Err(SchemaError::Invalid(_))),
+                "version {version} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn apply_json_is_migrated() {
+        let kind = r#"{"type": "Oval", "min": [0, 0], "max": [5, 5],
+            "color": {"r": 0, "g": 0, "b": 1, "a": 1}, "thickness": 2}"#;
+        let kinds = parse_apply_json(&format!("[{kind}]")).unwrap();
+        assert!(matches!(kinds[..], [AnnotationKind::Oval { fill: Fill::None, .. }]));
+
+        let kinds = parse_apply_json(V0_RECTANGLE).unwrap();
+        assert!(matches!(kinds[..], [AnnotationKind::Rectangle { .. }]));
+
+        let too_new = format!(r#"{{"version": {}, "annotations": []}}"#, ANNOTZ_VERSION + 1);
+        assert!(parse_apply_json(&too_new).is_err());
+    }
+
+    #[test]
+    fn apply_refuses_values_a_sidecar_cannot_hold() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        for bad in [
+            args(&["--arrow", "NaN,1:5,5"]),
+            args(&["--rect", "0,0:inf,5"]),
+            args(&["--fill", "blur:NaN", "--rect", "0,0:5,5"]),
+            args(&["--fill", "blur:1e9", "--rect", "0,0:5,5"]),
+            args(&["--thickness", "NaN", "--rect", "0,0:5,5"]),
+            args(&["--thickness", "-3", "--rect", "0,0:5,5"]),
+            args(&["--font-size", "1000", "--text", "0,0:hi"]),
+        ] {
+            assert!(parse_apply_args(&bad).is_err(), "{bad:?} was accepted");
+        }
+        assert!(parse_calibration("inf=5mm").is_err());
+
+        let rect = |thickness: &str| {
+            format!(
+                r#"[{{"type": "Rectangle", "min": [0, 0], "max": [5, 5],
+                "color": {{"r": 0, "g": 0, "b": 1, "a": 1}}, "thickness": {thickness}}}]"#
+            )
+        };
+        assert!(parse_apply_json(&rect("2")).is_ok());
+        for thickness in ["-3", "1e39", "100"] {
+            assert!(parse_apply_json(&rect(thickness)).is_err(), "thickness {thickness} was accepted");
+        }
+    }
+
+    // ── Atomic writes & backups ─────────────────────────────────────────────
+
+    #[test]
+    fn write_atomic_replaces_the_file_and_leaves_no_temporary() {
+        let dir = scratch_dir("write-atomic");
+        let path = dir.join("a.png.annotz");
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        let names: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["a.png.annotz"]);
+    }
+
+    #[test]
+    fn temporaries_for_the_same_file_do_not_collide() {
+        let dir = scratch_dir("write-atomic-temporaries");
+        let path = dir.join("a.png.annotz");
+        let (first, _) = create_temporary(&path).unwrap();
+        let (second, _) = create_temporary(&path).unwrap();
+        assert_ne!(first, second);
+        assert!(first.exists() && second.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = scratch_dir("write-atomic-permissions");
+        let path = dir.join("a.png.annotz");
+        std::fs::write(&path, b"old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path, b"new").unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    /// The only test that sets the backup environment variables, so no
+    /// other test sees them change.
+    #[test]
+    fn backup_ring_keeps_the_newest_copies() {
+        let dir = scratch_dir("backups");
+        std::env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+        std::env::set_var("ANNOTATE_EDIT_BACKUPS", "3");
+        let sidecar = dir.join("a.png.annotz");
+        let backups = backup_dir(&sidecar);
+        assert!(backups.starts_with(dir.join("cache")));
+        let stamps = || {
+            let mut names: Vec<String> = std::fs::read_dir(&backups)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Nothing to back up before the first save.
+        backup_sidecar_at(&sidecar, 1000).unwrap();
+        assert!(!backups.exists());
+
+        std::fs::write(&sidecar, "v1").unwrap();
+        backup_sidecar_at(&sidecar, 1000).unwrap();
+        // Too soon after the last copy.
+        backup_sidecar_at(&sidecar, 1000 + BACKUP_INTERVAL_SECS - 1).unwrap();
+        assert_eq!(stamps(), ["1000.annotz"]);
+
+        for (i, now) in [2000, 3000, 4000].into_iter().enumerate() {
+            std::fs::write(&sidecar, format!("v{}", i + 2)).unwrap();
+            backup_sidecar_at(&sidecar, now).unwrap();
+        }
+        assert_eq!(stamps(), ["2000.annotz", "3000.annotz", "4000.annotz"]);
+        assert_eq!(std::fs::read_to_string(backups.join("4000.annotz")).unwrap(), "v4");
+
+        // The clock went back: the newest copy is in the future.
+        backup_sidecar_at(&sidecar, 3500).unwrap();
+        assert_eq!(stamps(), ["3000.annotz", "3500.annotz", "4000.annotz"]);
+        backup_sidecar_at(&sidecar, 3500 + BACKUP_INTERVAL_SECS - 1).unwrap();
+        assert_eq!(stamps(), ["3000.annotz", "3500.annotz", "4000.annotz"]);
+
+        std::env::set_var("ANNOTATE_EDIT_BACKUPS", "0");
+        backup_sidecar_at(&sidecar, 5000).unwrap();
+        assert_eq!(stamps().len(), 3);
+
+        // A cache dir that can't be created fails the backup, not the save.
+        std::env::set_var("ANNOTATE_EDIT_BACKUPS", "3");
+        std::env::set_var("XDG_CACHE_HOME", &sidecar);
+        let saved = save_annotations(&dir.join("a.png"), &[], None).unwrap();
+        assert!(saved.is_some_and(|e| e.starts_with("could not back up")));
+        assert!(load_annotations(&dir.join("a.png")).unwrap().annotations.is_empty());
+    }
+
+    // ── Clipboard images ────────────────────────────────────────────────────
+
+    #[test]
+    fn clipboard_images_get_fresh_names() {
+        let dir = scratch_dir("clipboard");
+        let first = new_clipboard_path(&dir, 42).unwrap();
+        let second = new_clipboard_path(&dir, 42).unwrap();
+        assert_ne!(first, second);
+
+        // A sidecar left behind under the next name must not be picked up.
+        let next = dir.join("clipboard-42-2.png");
+        std::fs::write(annotz_path(&next), "{}").unwrap();
+        let third = new_clipboard_path(&dir, 42).unwrap();
+        assert_eq!(third, dir.join("clipboard-42-3.png"));
+    }
+
+    // ── Redaction ───────────────────────────────────────────────────────────
+
+    /// An image with no two neighbouring pixels alike.
+    fn varied_image() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(40, 30, |x, y| {
+            image::Rgba([(x * 6) as u8, (y * 8) as u8, ((x * 7 + y * 13) % 256) as u8, 255])
+        }))
+    }
+
+    fn filled_rect(fill: Fill) -> Annotation {
+        Annotation {
+            kind: AnnotationKind::Rectangle {
+                min: (4.0, 4.0),
+                max: (24.0, 20.0),
+                color: Color4::default(),
+                thickness: 2.0,
+                fill,
+                style: StrokeStyle::Solid,
+            },
+        }
+    }
+
+    #[test]
+    fn pixelate_blocks_below_the_minimum_are_refused() {
+        let raw = varied_image();
+        for block in [0, 1] {
+            assert!(parse_fill(&format!("pixelate:{block}")).is_err());
+            assert!(serde_json::from_str::<Fill>(&format!(r#"{{"Pixelate": {block}}}"#)).is_err());
+            assert!(
+                redact_source(&raw, &[filled_rect(Fill::Pixelate(block))]).is_err(),
+                "block size {block} was exported"
+            );
+        }
+    }
+
+    #[test]
+    fn pixelate_blocks_of_two_average_each_block() {
+        assert!(matches!(parse_fill("pixelate:2"), Ok(Fill::Pixelate(2))));
+        assert!(matches!(serde_json::from_str::<Fill>(r#"{"Pixelate": 2}"#), Ok(Fill::Pixelate(2))));
+
+        let raw = varied_image();
+        let out = redact_source(&raw, &[filled_rect(Fill::Pixelate(2))]).unwrap().to_rgba8();
+        let original = raw.to_rgba8();
+        // The block at (10, 10)..(12, 12) lies inside the rectangle.
+        let block = [(10, 10), (11, 10), (10, 11), (11, 11)].map(|(x, y)| *out.get_pixel(x, y));
+        assert!(block.iter().all(|p| *p == block[0]));
+        assert_ne!(*out.get_pixel(10, 10), *original.get_pixel(10, 10));
+        // Outside the rectangle the image is untouched.
+        assert_eq!(*out.get_pixel(35, 25), *original.get_pixel(35, 25));
+    }
+
+    #[test]
+    fn redact_fill_blacks_out_every_covered_pixel() {
+        let raw = varied_image();
+        let out = redact_source(&raw, &[filled_rect(Fill::Redact)]).unwrap().to_rgba8();
+        for y in 4..20 {
+            for x in 4..24 {
+                assert_eq!(*out.get_pixel(x, y), image::Rgba([0, 0, 0, 255]));
+            }
+        }
+        assert_eq!(*out.get_pixel(35, 25), *raw.to_rgba8().get_pixel(35, 25));
+    }
+
+    #[test]
+    fn export_source_is_redacted_before_the_spotlight() {
+        let raw = varied_image();
+        let annotations = [
+            filled_rect(Fill::Redact),
+            filled_rect(Fill::Spotlight {
+                dim: 0.5,
+                grayscale: false,
+            }),
+        ];
+        let out = export_source(&raw, &annotations).unwrap();
+        // Lit by the spotlight, the redacted pixels stay black.
+        assert_eq!(*out.get_pixel(10, 10), image::Rgba([0, 0, 0, 255]));
+        assert_ne!(*out.get_pixel(35, 25), *raw.to_rgba8().get_pixel(35, 25));
+    }
+
+    #[test]
+    fn blur_covers_annotations_beneath_it() {
+        let raw = varied_image();
+        let red = Color4 {
+            r: 1.0,
+            g: 0.0,
+            b: 0.0,
+            a: 1.0,
+        };
+        let under = Annotation {
+            kind: AnnotationKind::Rectangle {
+                min: (8.0, 8.0),
+                max: (16.0, 16.0),
+                color: red.clone(),
+                thickness: 2.0,
+                fill: Fill::Color(red),
+                style: StrokeStyle::Solid,
+            },
+        };
+        let blur = filled_rect(Fill::Blur(2.0));
+        let alone = render_annotated(&raw, std::slice::from_ref(&blur), None).unwrap();
+        let over = render_annotated(&raw, &[under.clone(), blur], None).unwrap();
+        let unblurred = render_annotated(&raw, &[under], None).unwrap();
+        assert_ne!(*unblurred.get_pixel(12, 12), *alone.get_pixel(12, 12));
+        // Inside the blur's outline, the rectangle beneath doesn't show.
+        for y in 6..18 {
+            for x in 6..22 {
+                assert_eq!(*over.get_pixel(x, y), *alone.get_pixel(x, y), "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn blurred_oval_edges_are_anti_aliased() {
+        let raw = varied_image().to_rgba8();
+        let (ox, oy, patch) = blurred_patch(&raw, 4.0, 4.0, 24.0, 20.0, 2.0, &FillMask::Oval).unwrap();
+        assert_eq!((ox, oy), (4, 4));
+        // Opaque at the center, clear in the corners, partly covered along
+        // the edge in between.
+        assert_eq!(patch.get_pixel(10, 8)[3], 255);
+        assert_eq!(patch.get_pixel(0, 0)[3], 0);
+        assert!(patch.pixels().any(|p| p[3] > 0 && p[3] < 255));
+    }
+
+    #[test]
+    fn canvas_highlighter_tint_matches_exported_multiply() {
+        let gray = RgbaImage::from_pixel(20, 20, image::Rgba([200, 200, 200, 255]));
+        let mut pixmap = rgba_to_pixmap(&gray).unwrap();
+        let source = pixmap.clone();
+        let color = Color4 {
+            r: 1.0,
+            g: 0.9,
+            b: 0.2,
+            a: 0.4,
+        };
+        let stroke = ScenePrim::Path {
+            points: vec![(0.0, 10.0), (20.0, 10.0)],
+            stroke: PrimStroke {
+                color: color.clone(),
+                width: 8.0,
+                style: StrokeStyle::Solid,
+            },
+            multiply: true,
+        };
+        rasterize_prims(&mut pixmap, &[stroke], &source);
+        let exported = pixmap_to_rgba(&pixmap);
+        let tint = multiply_tint(&color);
+        let on_canvas = [tint.r(), tint.g(), tint.b()].map(|k| (200.0 * f32::from(k) / 255.0).round() as i32);
+        let pixel = exported.get_pixel(10, 10);
+        for (channel, expected) in on_canvas.iter().enumerate() {
+            assert!((i32::from(pixel[channel]) - expected).abs() <= 2, "{pixel:?} vs {on_canvas:?}");
+        }
+    }
+
+    #[test]
+    fn source_key_follows_redactions_and_spotlights_only() {
+        let redacted = [filled_rect(Fill::Redact)];
+        let key = SourceKey::new(&redacted);
+        assert!(!key.is_plain());
+        assert_eq!(key, SourceKey::new(&[filled_rect(Fill::Redact), filled_rect(Fill::None)]));
+
+        let mut moved = filled_rect(Fill::Redact);
+        if let AnnotationKind::Rectangle { max, .. } = &mut moved.kind {
+            max.0 += 1.0;
+        }
+        assert_ne!(key, SourceKey::new(&[moved]));
+        assert_ne!(key, SourceKey::new(&[filled_rect(Fill::Pixelate(4))]));
+        assert!(SourceKey::new(&[filled_rect(Fill::Blur(3.0))]).is_plain());
+    }
+
+    #[test]
+    fn verify_redactions_catches_leftover_pixels() {
+        let original = varied_image().to_rgba8();
+        let size = original.dimensions();
+        let redactions = |effect| {
+            let area = redaction_area(size, (4.0, 4.0), (24.0, 20.0), &FillMask::Rect).unwrap();
+            vec![(area, effect)]
+        };
+
+        let area = &redactions(Redaction::Solid)[0].0;
+        let mut img = original.clone();
+        for (x, y, pixel) in img.enumerate_pixels_mut() {
+            if area.covers(x, y) {
+                *pixel = image::Rgba([0, 0, 0, 255]);
+            }
+        }
+        assert!(verify_redactions(&original, &img, &redactions(Redaction::Solid)).is_ok());
+        img.put_pixel(12, 12, *original.get_pixel(12, 12));
+        assert!(verify_redactions(&original, &img, &redactions(Redaction::Solid)).is_err());
+
+        // An untouched region passes the per-block check for one-pixel
+        // blocks, but is still caught.
+        assert!(verify_redactions(&original, &original, &redactions(Redaction::Pixelate(1))).is_err());
+    }
+
+    #[test]
+    fn pixelating_a_flat_region_is_allowed() {
+        let raw = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, image::Rgba([200, 200, 200, 255])));
+        assert!(redact_source(&raw, &[filled_rect(Fill::Pixelate(4))]).is_ok());
+    }
+
+    // ── Export metadata ─────────────────────────────────────────────────────
+
+    fn encoded(format: image::ImageFormat) -> Vec<u8> {
+        let img = DynamicImage::ImageRgb8(varied_image().to_rgb8());
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn png_text_chunks_are_refused() {
+        let png = encoded(image::ImageFormat::Png);
+        assert!(verify_no_metadata(ExportFormat::Png, &png).is_ok());
+        // Signature (8 bytes) and IHDR (25 bytes), then a tEXt chunk.
+        let text = b"Comment\0taken on host-42";
+        let mut tampered = png[..33].to_vec();
+        tampered.extend((text.len() as u32).to_be_bytes());
+        tampered.extend(b"tEXt");
+        tampered.extend(text);
+        tampered.extend([0; 4]);
+        tampered.extend(&png[33..]);
+        assert!(verify_no_metadata(ExportFormat::Png, &tampered).is_err());
+    }
+
+    #[test]
+    fn jpeg_exif_segments_are_refused() {
+        let jpeg = encoded(image::ImageFormat::Jpeg);
+        assert!(verify_no_metadata(ExportFormat::Jpeg, &jpeg).is_ok());
+        let exif = b"Exif\0\0MM\0*";
+        let mut tampered = jpeg[..2].to_vec();
+        tampered.extend([0xFF, 0xE1]);
+        tampered.extend((exif.len() as u16 + 2).to_be_bytes());
+        tampered.extend(exif);
+        tampered.extend(&jpeg[2..]);
+        assert!(verify_no_metadata(ExportFormat::Jpeg, &tampered).is_err());
+    }
+
+    #[test]
+    fn webp_exif_chunks_are_refused() {
+        let webp = encoded(image::ImageFormat::WebP);
+        assert!(verify_no_metadata(ExportFormat::Webp, &webp).is_ok());
+        let mut tampered = webp.clone();
+        tampered.extend(b"EXIF");
+        tampered.extend(4u32.to_le_bytes());
+        tampered.extend(b"MM\0*");
+        assert!(verify_no_metadata(ExportFormat::Webp, &tampered).is_err());
+    }
+
+    // ── Highlighters ────────────────────────────────────────────────────────
+
+    #[test]
+    fn editing_a_highlighter_keeps_its_width_scale() {
+        let mut app = AnnotateApp::new(None);
+        app.annotations.push(Annotation {
+            kind: AnnotationKind::Path {
+                points: vec![(0.0, 0.0), (10.0, 0.0)],
+                color: Color4::default(),
+                thickness: 12.0 * HIGHLIGHTER_WIDTH_SCALE,
+                highlight: true,
+            },
+        });
+        app.selected.insert(0);
+        assert_eq!(app.annotations[0].kind.thickness_setting(), Some(12.0));
+
+        app.edit_selected(|kind| kind.set_thickness_setting(15.0));
+        let AnnotationKind::Path { thickness, .. } = app.annotations[0].kind else {
+            unreachable!()
+        };
+        assert_eq!(thickness, 15.0 * HIGHLIGHTER_WIDTH_SCALE);
+    }
+
+    // ── Steps ───────────────────────────────────────────────────────────────
+
+    /// A step told apart from the others by `id`, its x position.
+    fn step(id: u32, number: u32) -> Annotation {
+        Annotation {
+            kind: AnnotationKind::Step {
+                pos: (id as f32, 0.0),
+                number,
+                color: Color4::default(),
+                font_size: 20.0,
+            },
+        }
+    }
+
+    /// The ids of the steps, in the order of their numbers, which must run
+    /// 1, 2, 3, ...
+    fn step_order(annotations: &[Annotation]) -> Vec<u32> {
+        let mut steps: Vec<(u32, u32)> = annotations
+            .iter()
+            .filter_map(|ann| match ann.kind {
+                AnnotationKind::Step { pos, number, .. } => Some((number, pos.0 as u32)),
+                _ => None,
+            })
+            .collect();
+        steps.sort();
+        assert!(steps.iter().map(|(number, _)| *number).eq(1..=steps.len() as u32));
+        steps.into_iter().map(|(_, id)| id).collect()
+    }
+
+    #[test]
+    fn deleting_a_step_closes_the_gap() {
+        let mut annotations = vec![step(10, 1), filled_rect(Fill::None), step(30, 3), step(20, 4)];
+        renumber_steps(&mut annotations, None);
+        assert_eq!(step_order(&annotations), [10, 30, 20]);
+    }
+
+    #[test]
+    fn a_moved_step_goes_before_or_after_the_one_it_displaced() {
+        // Step 3 moved to number 1 goes first.
+        let mut earlier = vec![step(10, 1), step(20, 2), step(30, 1)];
+        renumber_steps(&mut earlier, Some((2, 3)));
+        assert_eq!(step_order(&earlier), [30, 10, 20]);
+
+        // Step 1 moved to number 3 goes last.
+        let mut later = vec![step(10, 3), step(20, 2), step(30, 3)];
+        renumber_steps(&mut later, Some((0, 1)));
+        assert_eq!(step_order(&later), [20, 30, 10]);
+    }
+
+    #[test]
+    fn added_steps_continue_the_sequence() {
+        let existing = vec![step(10, 1), step(20, 2)];
+        let mut added = vec![step(40, 2), step(30, 1)];
+        number_added_steps(&existing, &mut added);
+        let mut all = existing;
+        all.extend(added);
+        assert_eq!(step_order(&all), [10, 20, 30, 40]);
+    }
+
+    // ── Text wrapping ───────────────────────────────────────────────────────
+
+    #[test]
+    fn wrap_text_breaks_at_spaces_within_the_width() {
+        let content = "the quick brown fox jumps over the lazy dog";
+        let wrapped = wrap_text(content, 20.0, 120.0);
+        assert!(wrapped.lines().count() > 1);
+        assert!(wrapped.lines().all(|line| text_width(line, 20.0) <= 120.0));
+        assert_eq!(wrapped.replace('\n', " "), content);
+    }
+
+    #[test]
+    fn wrap_text_keeps_line_breaks_and_short_text() {
+        assert_eq!(wrap_text("one\ntwo", 20.0, 500.0), "one\ntwo");
+        assert_eq!(wrap_text("", 20.0, 500.0), "");
+    }
+
+    #[test]
+    fn wrap_text_splits_words_longer_than_a_line() {
+        let word = "antidisestablishmentarianism";
+        let wrapped = wrap_text(word, 20.0, 60.0);
+        assert!(wrapped.lines().count() > 1);
+        assert!(wrapped.lines().all(|line| text_width(line, 20.0) <= 60.0));
+        assert_eq!(wrapped.replace('\n', ""), word);
+
+        // A line too narrow for any character still gets one per line.
+        assert_eq!(wrap_text("abc", 20.0, 1.0), "a\nb\nc");
+    }
+
+    #[test]
+    fn pdf_text_embeds_the_glyphs_it_shows() {
+        let raw = varied_image();
+        let text = Annotation {
+            kind: AnnotationKind::Text {
+                pos: (2.0, 2.0),
+                content: "Привет".into(),
+                font_size: 12.0,
+                color: Color4::default(),
+            },
+        };
+        let pdf = render_pdf(&[(&raw, std::slice::from_ref(&text), None)]).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Identity-H"));
+        assert!(pdf.contains("/CIDFontType2"));
+        // The ToUnicode map gives each glyph back its character.
+        for ch in "Привет".chars() {
+            let id = ab_glyph::Font::glyph_id(export_font(), ch).0;
+            assert_ne!(id, 0, "no glyph for {ch:?}");
+            assert!(pdf.contains(&format!("<{id:04X}> <{:04X}>", ch as u32)), "{ch:?} unmapped");
+        }
+    }
+
+    #[test]
+    fn pdf_writes_each_graphics_state_once_per_page() {
+        let raw = varied_image();
+        let translucent = |alpha: f32| {
+            filled_rect(Fill::Color(Color4 {
+                r: 0.0,
+                g: 0.0,
+                b: 1.0,
+                a: alpha,
+            }))
+        };
+        let annotations: Vec<Annotation> = (0..5).map(|_| translucent(0.5)).chain([translucent(0.25)]).collect();
+        let pdf = render_pdf(&[(&raw, &annotations, None)]).unwrap();
+        let states = String::from_utf8_lossy(&pdf).matches("/Type /ExtGState").count();
+        assert_eq!(states, 2);
+    }
+
+    #[test]
+    fn pdf_exports_a_negative_thickness() {
+        let raw = varied_image();
+        let mut rect = filled_rect(Fill::None);
+        if let AnnotationKind::Rectangle { thickness, .. } = &mut rect.kind {
+            *thickness = -3.0;
+        }
+        assert!(render_pdf(&[(&raw, std::slice::from_ref(&rect), None)]).is_ok());
+    }
+
+    /// The names of `describe`'s subcommands, each with its flags.
+    fn described_commands() -> Vec<(String, Vec<String>)> {
+        let describe: serde_json::Value = serde_json::from_str(DESCRIBE_JSON).unwrap();
+        let names = |args: &serde_json::Value| -> Vec<String> {
+            args.as_array().unwrap().iter().map(|arg| arg["name"].as_str().unwrap().to_owned()).collect()
+        };
+        describe["subcommands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|command| (command["name"].as_str().unwrap().to_owned(), names(&command["args"])))
+            .collect()
+    }
+
+    #[test]
+    fn describe_lists_the_export_commands() {
+        let commands = described_commands();
+        let flags = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert!(commands.contains(&("render".into(), flags(&["path", "out", "format", "link-image"]))));
+        assert!(commands.contains(&("pdf".into(), flags(&["out", "path"]))));
+        let describe: serde_json::Value = serde_json::from_str(DESCRIBE_JSON).unwrap();
+        let named = |list: &serde_json::Value, name: &str| {
+            list.as_array().unwrap().iter().find(|entry| entry["name"] == name).unwrap().clone()
+        };
+        let render = named(&describe["subcommands"], "render");
+        let format = named(&render["args"], "format");
+        for name in ["png", "jpeg", "webp", "svg", "pdf"] {
+            assert!(format["description"].as_str().unwrap().contains(name));
+            assert!(ExportFormat::parse(name).is_some());
+        }
+    }
+
+    #[test]
+    fn describe_lists_the_run_flags() {
+        let describe: serde_json::Value = serde_json::from_str(DESCRIBE_JSON).unwrap();
+        let args = describe["args"].as_array().unwrap();
+        let arg = |name: &str| args.iter().find(|arg| arg["name"] == name).unwrap();
+        assert_eq!(arg("path")["arity"], "optional");
+        assert_eq!(arg("clipboard")["type"], "boolean");
+        assert_eq!(arg("clipboard")["arity"], "flag");
+        assert_eq!(arg("clipboard")["mode"], "dashdash");
+    }
+
+    #[test]
+    fn describe_lists_only_flags_apply_accepts() {
+        let commands = described_commands();
+        let (_, flags) = commands.iter().find(|(name, _)| name == "apply").unwrap();
+        assert!(flags.contains(&"json".to_string()));
+        for flag in flags {
+            // Values may be rejected, but never the flag itself.
+            let args = vec![format!("--{flag}"), "?".to_string()];
+            if let Err(e) = parse_apply_args(&args) {
+                assert!(!e.starts_with("unknown argument"), "{e}");
+            }
+        }
+    }
+}